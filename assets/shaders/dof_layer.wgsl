@@ -0,0 +1,27 @@
+#import bevy_sprite::mesh2d_vertex_output::VertexOutput
+
+@group(2) @binding(0)
+var<uniform> radius: vec2<f32>;
+
+@group(2) @binding(1)
+var layer_texture: texture_2d<f32>;
+
+@group(2) @binding(2)
+var layer_sampler: sampler;
+
+const TAPS: u32 = 24u;
+const GOLDEN_ANGLE: f32 = 2.39996323;
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Golden angle spiral over a disc of the given radius, which gives an
+    // even enough bokeh without a separate blur pass.
+    var sum = vec4<f32>(0.0);
+    for (var i = 0u; i < TAPS; i++) {
+        let r = sqrt((f32(i) + 0.5) / f32(TAPS));
+        let theta = f32(i) * GOLDEN_ANGLE;
+        let offset = vec2<f32>(cos(theta), sin(theta)) * r * radius;
+        sum += textureSample(layer_texture, layer_sampler, in.uv + offset);
+    }
+    return sum / f32(TAPS);
+}
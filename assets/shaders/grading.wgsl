@@ -0,0 +1,28 @@
+#import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+
+@group(0) @binding(0) var screen_texture: texture_2d<f32>;
+@group(0) @binding(1) var screen_sampler: sampler;
+@group(0) @binding(2) var lut_texture: texture_3d<f32>;
+@group(0) @binding(3) var lut_sampler: sampler;
+@group(0) @binding(4) var<uniform> strength: f32;
+
+fn to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let high = 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055;
+    return select(high, color * 12.92, color <= vec3(0.0031308));
+}
+
+fn to_linear(color: vec3<f32>) -> vec3<f32> {
+    let high = pow((color + 0.055) / 1.055, vec3(2.4));
+    return select(high, color / 12.92, color <= vec3(0.04045));
+}
+
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(screen_texture, screen_sampler, in.uv);
+    // .cube LUTs map display encoded colors, sample at texel centers.
+    let size = f32(textureDimensions(lut_texture).x);
+    let encoded = to_srgb(clamp(color.rgb, vec3(0.0), vec3(1.0)));
+    let coords = encoded * (size - 1.0) / size + 0.5 / size;
+    let graded = to_linear(textureSample(lut_texture, lut_sampler, coords).rgb);
+    return vec4(mix(color.rgb, graded, strength), color.a);
+}
@@ -1,44 +1,96 @@
-#import bevy_pbr::mesh_view_bindings
+#import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+#import bevy_render::globals::Globals
 
-struct ShaderTime {
-    secs_since_startup: f32,
-    dt: f32,
-};
+@group(0) @binding(0) var screen_texture: texture_2d<f32>;
+@group(0) @binding(1) var screen_sampler: sampler;
+// [0]: curvature, corner radius, mask strength, mask size
+// [1]: bar strength, bar speed, bar height, flicker
+// [2]: glitch levels: tear, rgb split, frozen blocks, invert
+// [3]: glitch seed
+@group(0) @binding(2) var<uniform> crt: array<vec4<f32>, 4>;
+@group(0) @binding(3) var<uniform> globals: Globals;
+// The last frame from before frozen blocks started.
+@group(0) @binding(4) var history_texture: texture_2d<f32>;
 
-@group(1) @binding(0)
-var texture: texture_2d<f32>;
+// Positive curvature bulges the picture, negative pinches it.
+fn curve(uv: vec2<f32>, curvature: f32) -> vec2<f32> {
+    let centered = uv * 2.0 - 1.0;
+    return centered * (1.0 + curvature * dot(centered, centered)) * 0.5 + 0.5;
+}
+
+// 1.0 inside the rounded screen, fading to 0.0 over about a pixel.
+fn screen_edge(uv: vec2<f32>, radius: f32, size: vec2<f32>) -> f32 {
+    let p = (abs(uv - 0.5) - 0.5) * vec2(size.x / size.y, 1.0) + radius;
+    let distance = length(max(p, vec2(0.0))) + min(max(p.x, p.y), 0.0) - radius;
+    return clamp(-distance * size.y, 0.0, 1.0);
+}
+
+fn mask(position: vec2<f32>, strength: f32, size: f32) -> vec3<f32> {
+    var x = position.x;
+#ifdef SHADOW_MASK
+    // Every other row of triads is shifted by half a triad.
+    x += floor(position.y / size) % 2.0 * size * 0.5;
+#endif
+    let channel = u32(fract(x / size) * 3.0);
+    let lit = vec3(f32(channel == 0u), f32(channel == 1u), f32(channel == 2u));
+    // Keeps the average brightness.
+    return mix(vec3(1.0 - strength), vec3(1.0), lit) / (1.0 - strength * 2.0 / 3.0);
+}
 
-@group(1) @binding(1)
-var our_sampler: sampler;
+fn hash(n: f32) -> f32 {
+    return fract(sin(n * 12.9898) * 43758.5453);
+}
 
-//@group(1) @binding(2)
-//var<uniform> time: ShaderTime;
+fn sample(uv: vec2<f32>) -> vec3<f32> {
+    return textureSampleLevel(screen_texture, screen_sampler, uv, 0.0).rgb;
+}
 
 @fragment
-fn fragment(
-    @builtin(position) position: vec4<f32>,
-    #import bevy_sprite::mesh2d_vertex_output
-) -> @location(0) vec4<f32> {
-    // Get screen position with coordinates from 0 to 1
-    let uv = position.xy / vec2<f32>(view.width, view.height);
+fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(screen_texture));
+    let glitch = crt[2];
+    // Glitch patterns jump a few times a second.
+    let step = crt[3].x + floor(globals.time * 15.0);
+#ifdef CRT
+    var uv = curve(in.uv, crt[0].x);
+#else
+    // Glitches only, the CRT is off.
+    var uv = in.uv;
+#endif
+    let screen_uv = uv;
+
+    // Tearing: some horizontal slices shift sideways.
+    let slice = hash(floor(uv.y * 40.0) + step);
+    if slice > 1.0 - glitch.x * 0.5 {
+        uv.x += (hash(slice + step) - 0.5) * 0.15 * glitch.x;
+    }
+
+    // RGB split: red and blue drift apart.
+    let split = vec2(0.01 * glitch.y * (1.0 + hash(step)), 0.0);
+    var color = vec3(sample(uv + split).r, sample(uv).g, sample(uv - split).b);
+
+    // Frozen blocks keep showing the frame from before the burst.
+    let block = floor(uv * vec2(16.0, 9.0));
+    if hash(block.x * 17.0 + block.y * 131.0 + crt[3].x) < glitch.z * 0.4 {
+        color = textureSampleLevel(history_texture, screen_sampler, uv, 0.0).rgb;
+    }
 
-    /*let col_in = textureSample(texture, our_sampler, uv);
+    color = mix(color, vec3(1.0) - min(color, vec3(1.0)), glitch.w);
 
-    let strength = 8.0;
-    
-    let x = (uv.x + 4.0 ) * (uv.y + 4.0 ) * (floor((time.secs_since_startup * 10.0)));
-    let pwr = (((((x % 13.0) + 1.0) * ((x % 123.0) + 1.0)) % 0.01) - 0.005) * strength;
-	let grain = vec3(pwr);
+#ifdef CRT
+#ifdef CRT_MASK
+    color *= mask(in.position.xy, crt[0].z, crt[0].w);
+#endif
 
-    let col_grain = col_in + (vec4(grain, 1.0));
+    // Rolling interference bar, a soft band moving down the screen.
+    let bar_distance = abs(fract(screen_uv.y - globals.time * crt[1].y) - 0.5) * 2.0;
+    color *= 1.0 + crt[1].x * (1.0 - smoothstep(0.0, crt[1].z, bar_distance));
 
-    let viguv = uv * (1. - uv.yx);
-    let vig = viguv.x*viguv.y * 10.0;
-    let pvig = pow(vig, 0.25);
+    // A new brightness every frame.
+    color *= 1.0 - crt[1].w * hash(f32(globals.frame_count % 1000u));
 
-    // Sample each color channel with an arbitrary shift
-    var output_color = col_grain * pvig;
-    //output_color = vec4(pvig);*/
+    color *= screen_edge(screen_uv, crt[0].y, size);
+#endif
 
-    return textureSample(texture, our_sampler, uv);
+    return vec4(color, 1.0);
 }
@@ -0,0 +1,144 @@
+#import bevy_sprite::{
+    mesh2d_vertex_output::VertexOutput,
+    mesh2d_view_bindings::globals,
+}
+
+// Rows of `rain`, filled by `matrix_shader_rain::ShaderRain::params`.
+// Area of the quad in field space: min, size.
+const AREA: u32 = 0u;
+// Head to body fade time, shrink time, flip interval.
+const TIMING: u32 = 1u;
+// The field's `FlipCurve`: head, body, freeze age, falloff.
+const FLIP: u32 = 2u;
+// Letters per second and lifetime ranges: min, max, min, max.
+const DROPS: u32 = 3u;
+// Spawn height range, max length, and the seconds between two drops of a
+// column times its cell size.
+const COLUMNS: u32 = 4u;
+// Glyph atlas columns, rows and glyph count.
+const ATLAS: u32 = 5u;
+// Per layer, far to near: drift velocity, cell size, depth scale; then the
+// head and body colors.
+const LAYER: u32 = 6u;
+const LAYERS: u32 = 3u;
+// Drops are evaluated for this many cycles back, so drops outliving their
+// cycle aren't cut off.
+const CYCLES: u32 = 3u;
+
+@group(2) @binding(0)
+var<uniform> rain: array<vec4<f32>, 15>;
+
+@group(2) @binding(1)
+var atlas_texture: texture_2d<f32>;
+
+@group(2) @binding(2)
+var atlas_sampler: sampler;
+
+fn hash(x: u32) -> u32 {
+    var h = x * 747796405u + 2891336453u;
+    h = ((h >> ((h >> 28u) + 4u)) ^ h) * 277803737u;
+    return (h >> 22u) ^ h;
+}
+
+fn hash3(a: u32, b: u32, c: u32) -> u32 {
+    return hash(a ^ hash(b ^ hash(c)));
+}
+
+fn unit(h: u32) -> f32 {
+    return f32(h) / 4294967295.0;
+}
+
+// Glyph changes of a letter `age` seconds old, which was the head for
+// `head_time` seconds. Integrates the flip curve, so the count only ever
+// grows while the rate falls.
+fn flips(age: f32, head_time: f32) -> f32 {
+    let curve = rain[FLIP];
+    let head = min(age, head_time);
+    let freeze = max(curve.z, 1e-4);
+    let exponent = curve.w + 1.0;
+    let body_start = 1.0 - pow(1.0 - min(head, freeze) / freeze, exponent);
+    let body_end = 1.0 - pow(1.0 - min(age, freeze) / freeze, exponent);
+    let body = curve.y * freeze / exponent * (body_end - body_start);
+    return (curve.x * head + body) / max(rain[TIMING].z, 1e-4);
+}
+
+// Premultiplied color of the drop of `cycle` in column `column` at `q`,
+// `time` seconds into the cycle.
+fn drop(layer: u32, column: i32, cycle: i32, time: f32, q: vec2<f32>) -> vec4<f32> {
+    let grid = rain[LAYER + layer * 3u];
+    let cell = grid.z;
+    let columns = rain[COLUMNS];
+    let period = columns.w / cell;
+    let h = hash3(layer, bitcast<u32>(column), bitcast<u32>(cycle));
+
+    let drops = rain[DROPS];
+    let rate = mix(drops.x, drops.y, unit(hash(h ^ 1u)));
+    let lifetime = mix(drops.z, drops.w, unit(hash(h ^ 2u))) / grid.w;
+    let top = mix(columns.x, columns.y, unit(hash(h ^ 3u)));
+    let start = unit(hash(h ^ 4u)) * period;
+
+    let index = floor((top - q.y) / cell + 0.5);
+    if index < 0.0 || index >= columns.z {
+        return vec4<f32>(0.0);
+    }
+    let timing = rain[TIMING];
+    let age = time - start - (index + 1.0) / rate;
+    if age < 0.0 || age >= lifetime + timing.y {
+        return vec4<f32>(0.0);
+    }
+
+    // The last letter keeps the head color.
+    let head_time = select(1.0 / rate, 1e9, index >= columns.z - 1.0);
+    let fade = clamp((age - head_time) / max(timing.x, 1e-4), 0.0, 1.0);
+    let color = mix(
+        rain[LAYER + layer * 3u + 1u],
+        rain[LAYER + layer * 3u + 2u],
+        1.0 - (1.0 - fade) * (1.0 - fade),
+    );
+    let size = 1.0 - clamp((age - lifetime) / max(timing.y, 1e-4), 0.0, 1.0);
+
+    let center = vec2<f32>((f32(column) + 0.5) * cell, top - index * cell);
+    let local = (q - center) / (cell * max(size, 1e-4)) + 0.5;
+    if any(local < vec2<f32>(0.0)) || any(local >= vec2<f32>(1.0)) {
+        return vec4<f32>(0.0);
+    }
+
+    let atlas = rain[ATLAS];
+    let letter = hash3(h, u32(index), 5u);
+    let phase = flips(age, head_time) + unit(letter);
+    let glyph = hash3(letter, u32(phase), 6u) % u32(atlas.z);
+    let tile = vec2<f32>(f32(glyph % u32(atlas.x)), f32(glyph / u32(atlas.x)));
+    let texel = 0.5 / f32(textureDimensions(atlas_texture).y) * atlas.y;
+    let within = clamp(vec2<f32>(local.x, 1.0 - local.y), vec2<f32>(texel), vec2<f32>(1.0 - texel));
+    let coverage = textureSampleLevel(atlas_texture, atlas_sampler, (tile + within) / atlas.xy, 0.0).r;
+    return vec4<f32>(color.rgb * color.a, color.a) * coverage;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    if rain[ATLAS].z < 1.0 {
+        return vec4<f32>(0.0);
+    }
+    let area = rain[AREA];
+    let p = area.xy + vec2<f32>(in.uv.x, 1.0 - in.uv.y) * area.zw;
+    let seconds = globals.time;
+
+    var out = vec4<f32>(0.0);
+    for (var layer = 0u; layer < LAYERS; layer++) {
+        let grid = rain[LAYER + layer * 3u];
+        let period = max(rain[COLUMNS].w / grid.z, 1e-3);
+        let current = floor(seconds / period);
+        var color = vec4<f32>(0.0);
+        for (var back = 0u; back < CYCLES; back++) {
+            let cycle = current - f32(back);
+            let time = seconds - cycle * period;
+            // Drops drift from where their cycle started.
+            let q = p - grid.xy * time;
+            let column = i32(floor(q.x / grid.z));
+            let letter = drop(layer, column, i32(cycle), time, q);
+            color += letter * (1.0 - color.a);
+        }
+        out = color + out * (1.0 - color.a);
+    }
+    return out;
+}
@@ -0,0 +1,85 @@
+//! Runs the rain headless and prints timings as JSON or CSV.
+//!
+//! ```text
+//! cargo run --release --bin bench -- --densities 20,50,100 --seconds 30 --seed 0 --format csv
+//! ```
+
+use std::{fs, process::ExitCode};
+
+use bevy_matrix::matrix_bench::*;
+
+const USAGE: &str = "usage: bench [--densities 20,50,100] [--seconds 30] [--fps 60] [--seed 0] \
+[--format json|csv] [--output FILE]";
+
+fn parse<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("{flag} needs a value"))?;
+    value
+        .parse()
+        .map_err(|_| format!("invalid value for {flag}: {value}"))
+}
+
+fn run() -> Result<(), String> {
+    let mut settings = BenchSettings::default();
+    let mut csv = false;
+    let mut output = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--densities" => {
+                let value: String = parse(&flag, args.next())?;
+                settings.densities = value
+                    .split(',')
+                    .map(|d| parse(&flag, Some(d.trim().to_string())))
+                    .collect::<Result<_, _>>()?;
+            }
+            "--seconds" => settings.seconds = parse(&flag, args.next())?,
+            "--fps" => settings.fps = parse(&flag, args.next())?,
+            "--seed" => settings.seed = parse(&flag, args.next())?,
+            "--format" => match parse::<String>(&flag, args.next())?.as_str() {
+                "json" => csv = false,
+                "csv" => csv = true,
+                format => return Err(format!("unknown format: {format}")),
+            },
+            "--output" => output = Some(parse::<String>(&flag, args.next())?),
+            _ => return Err(format!("unknown argument: {flag}\n{USAGE}")),
+        }
+    }
+
+    if !(settings.fps.is_finite() && settings.fps > 0.0) {
+        return Err(format!(
+            "--fps needs a positive number, got {}\n{USAGE}",
+            settings.fps
+        ));
+    }
+    if !(settings.seconds.is_finite() && settings.seconds >= 0.0) {
+        return Err(format!(
+            "--seconds can't be negative, got {}\n{USAGE}",
+            settings.seconds
+        ));
+    }
+
+    let report = run_bench(&settings);
+    let text = if csv {
+        report.to_csv()
+    } else {
+        report.to_json()?
+    };
+    match output {
+        Some(path) => fs::write(&path, text).map_err(|e| format!("{path}: {e}")),
+        None => {
+            println!("{text}");
+            Ok(())
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{error}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,394 @@
+use std::time::Duration;
+
+use bevy::{
+    prelude::*,
+    render::{
+        camera::RenderTarget,
+        mesh::MeshVertexBufferLayoutRef,
+        render_asset::RenderAssetUsages,
+        render_resource::{
+            AsBindGroup, BlendState, Extent3d, RenderPipelineDescriptor, ShaderRef,
+            SpecializedMeshPipelineError, TextureDimension, TextureFormat, TextureUsages,
+        },
+        view::RenderLayers,
+    },
+    sprite::{Material2d, Material2dKey, Material2dPlugin, MaterialMesh2dBundle, Mesh2dHandle},
+};
+use bevy_tweening::*;
+
+use crate::matrix_strip::{MatrixStrip, Spawning};
+
+/// Depth-of-field settings, placed on the camera that looks at the rain.
+///
+/// Strips are sorted into `layers` depth buckets between `near` and `far`.
+/// Every bucket is rendered by its own camera into an offscreen image, which
+/// is composited back in front of the main camera with a blur radius
+/// proportional to the bucket's distance from `focal_depth`.
+#[derive(Component, Reflect, Clone)]
+#[reflect(Component, Default)]
+pub struct MatrixDof {
+    pub focal_depth: f32,
+    /// Blur radius, as a fraction of the view height, per unit of depth
+    /// away from the focal plane.
+    pub aperture: f32,
+    pub max_blur: f32,
+    pub near: f32,
+    pub far: f32,
+    /// At least 1.
+    pub layers: usize,
+    /// First render layer used for the depth buckets. The composite quads
+    /// live on the layer right below it, so it has to be at least 2 to
+    /// leave layer 0 to the scene.
+    pub first_layer: usize,
+}
+
+impl Default for MatrixDof {
+    fn default() -> Self {
+        Self {
+            focal_depth: 0.0,
+            aperture: 0.004,
+            max_blur: 0.02,
+            near: 1.0,
+            far: -4.0,
+            layers: 5,
+            first_layer: 24,
+        }
+    }
+}
+
+pub struct DofFocusLens {
+    pub start: f32,
+    pub end: f32,
+}
+
+impl Lens<MatrixDof> for DofFocusLens {
+    fn lerp(&mut self, target: &mut dyn Targetable<MatrixDof>, ratio: f32) {
+        target.focal_depth = self.start + (self.end - self.start) * ratio;
+    }
+}
+
+impl MatrixDof {
+    pub fn with_focal_depth(mut self, focal_depth: f32) -> Self {
+        self.focal_depth = focal_depth;
+        self
+    }
+
+    pub fn with_aperture(mut self, aperture: f32) -> Self {
+        self.aperture = aperture;
+        self
+    }
+
+    pub fn with_layers(mut self, layers: usize) -> Self {
+        self.layers = layers.max(1);
+        self
+    }
+
+    /// Animator that pulls `focal_depth` towards `target` over `seconds`.
+    /// Insert it on the camera carrying this component.
+    pub fn focus_pull(&self, target: f32, seconds: f32) -> Animator<MatrixDof> {
+        let tween = Tween::new(
+            EaseFunction::QuadraticInOut,
+            Duration::from_secs_f32(seconds),
+            DofFocusLens {
+                start: self.focal_depth,
+                end: target,
+            },
+        )
+        .with_repeat_count(RepeatCount::Finite(1));
+        Animator::new(tween)
+    }
+
+    /// Whether the layers can be set up. Cameras with settings that can't
+    /// get no depth of field.
+    pub fn check(&self) -> Result<(), String> {
+        if self.layers == 0 {
+            return Err("MatrixDof needs at least 1 layer".into());
+        }
+        if self.first_layer < 2 {
+            return Err(format!(
+                "MatrixDof first_layer is {}, it needs to be at least 2",
+                self.first_layer
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn composite_layer(&self) -> usize {
+        self.first_layer.saturating_sub(1)
+    }
+
+    /// Depth bucket a strip at depth `z` is rendered into.
+    pub fn bucket(&self, z: f32) -> usize {
+        let t = (z - self.far) / (self.near - self.far);
+        ((t * self.layers as f32) as usize).min(self.layers.saturating_sub(1))
+    }
+
+    /// Depth at the center of `bucket`.
+    pub fn bucket_depth(&self, bucket: usize) -> f32 {
+        let t = (bucket as f32 + 0.5) / self.layers as f32;
+        self.far + t * (self.near - self.far)
+    }
+
+    /// Blur radius, as a fraction of the view height, for things at depth `z`.
+    pub fn blur(&self, z: f32) -> f32 {
+        ((z - self.focal_depth).abs() * self.aperture).min(self.max_blur)
+    }
+}
+
+#[derive(Asset, TypePath, AsBindGroup, Clone)]
+pub struct DofLayerMaterial {
+    #[uniform(0)]
+    radius: Vec2,
+    #[texture(1)]
+    #[sampler(2)]
+    texture: Handle<Image>,
+}
+
+impl Material2d for DofLayerMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/dof_layer.wgsl".into()
+    }
+
+    fn specialize(
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayoutRef,
+        _key: Material2dKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        // The layer images hold premultiplied color, since the letters were
+        // already alpha blended onto a transparent clear color.
+        if let Some(target) = descriptor
+            .fragment
+            .as_mut()
+            .and_then(|fragment| fragment.targets[0].as_mut())
+        {
+            target.blend = Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Component)]
+struct DofLayerCamera;
+
+#[derive(Component)]
+struct DofLayerQuad {
+    bucket: usize,
+}
+
+#[derive(Component)]
+struct DofLayerImages(Vec<Handle<Image>>);
+
+pub struct MatrixDofPlugin;
+
+fn layer_image(size: UVec2) -> Image {
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: size.x.max(1),
+            height: size.y.max(1),
+            ..default()
+        },
+        TextureDimension::D2,
+        &[0; 8],
+        TextureFormat::Rgba16Float,
+        RenderAssetUsages::default(),
+    );
+    image.texture_descriptor.usage =
+        TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST | TextureUsages::RENDER_ATTACHMENT;
+    image
+}
+
+fn setup_dof_layers(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<DofLayerMaterial>>,
+    query: Query<(Entity, &MatrixDof, &Camera, &OrthographicProjection), Added<MatrixDof>>,
+) {
+    for (entity, dof, camera, projection) in &query {
+        if let Err(e) = dof.check() {
+            warn!("{e}, leaving depth of field off");
+            continue;
+        }
+        let size = camera.physical_viewport_size().unwrap_or(UVec2::ONE);
+        let quad = meshes.add(Rectangle::new(1.0, 1.0));
+        let mut handles = Vec::with_capacity(dof.layers);
+
+        commands
+            .entity(entity)
+            .insert(RenderLayers::layer(0).with(dof.composite_layer()));
+
+        for bucket in 0..dof.layers {
+            let image = images.add(layer_image(size));
+            handles.push(image.clone());
+
+            let layer_camera = commands
+                .spawn((
+                    Camera2dBundle {
+                        camera: Camera {
+                            hdr: true,
+                            order: -1 - (dof.layers - bucket) as isize,
+                            target: RenderTarget::Image(image.clone()),
+                            clear_color: ClearColorConfig::Custom(Color::NONE),
+                            ..default()
+                        },
+                        projection: projection.clone(),
+                        tonemapping: bevy::core_pipeline::tonemapping::Tonemapping::None,
+                        ..default()
+                    },
+                    RenderLayers::layer(dof.first_layer + bucket),
+                    DofLayerCamera,
+                ))
+                .id();
+
+            let material = materials.add(DofLayerMaterial {
+                radius: Vec2::ZERO,
+                texture: image,
+            });
+            let layer_quad = commands
+                .spawn((
+                    MaterialMesh2dBundle {
+                        mesh: Mesh2dHandle(quad.clone()),
+                        material,
+                        // Far buckets first, in front of the camera's near plane.
+                        transform: Transform::from_xyz(0.0, 0.0, -50.0 + bucket as f32),
+                        ..default()
+                    },
+                    RenderLayers::layer(dof.composite_layer()),
+                    DofLayerQuad { bucket },
+                ))
+                .id();
+
+            commands
+                .entity(entity)
+                .push_children(&[layer_camera, layer_quad]);
+        }
+        commands.entity(entity).insert(DofLayerImages(handles));
+    }
+}
+
+/// Strips can only be on one camera's depth buckets, so with several
+/// `MatrixDof` cameras the first one sorts them.
+fn assign_dof_layers(
+    mut commands: Commands,
+    dof: Query<&MatrixDof>,
+    mut warned: Local<bool>,
+    // Pooled strips are reused without re-adding `MatrixStrip`, but
+    // `Spawning` is added again every time.
+    strips: Query<
+        (Entity, &Transform),
+        (With<MatrixStrip>, Added<Spawning>, Without<RenderLayers>),
+    >,
+) {
+    let mut cameras = dof.iter();
+    let Some(dof) = cameras.next().filter(|dof| dof.check().is_ok()) else {
+        return;
+    };
+    if cameras.next().is_some() && !*warned {
+        warn!("Several MatrixDof cameras, only the first one sorts strips into depth layers");
+        *warned = true;
+    }
+    for (entity, transform) in &strips {
+        let bucket = dof.bucket(transform.translation.z);
+        commands
+            .entity(entity)
+            .insert(RenderLayers::layer(dof.first_layer + bucket));
+    }
+}
+
+fn sync_dof_layers(
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<DofLayerMaterial>>,
+    main: Query<(
+        &MatrixDof,
+        &Camera,
+        Ref<OrthographicProjection>,
+        &DofLayerImages,
+        &Children,
+    )>,
+    mut cameras: Query<(&DofLayerCamera, &mut OrthographicProjection), Without<MatrixDof>>,
+    mut quads: Query<(&DofLayerQuad, &Handle<DofLayerMaterial>, &mut Transform)>,
+) {
+    for (dof, camera, projection, layer_images, children) in &main {
+        let Some(size) = camera.physical_viewport_size() else {
+            continue;
+        };
+        for handle in &layer_images.0 {
+            if images.get(handle).is_some_and(|image| image.size() != size) {
+                images.get_mut(handle).unwrap().resize(Extent3d {
+                    width: size.x.max(1),
+                    height: size.y.max(1),
+                    ..default()
+                });
+            }
+        }
+
+        let area = projection.area.size();
+        let aspect = size.y as f32 / size.x.max(1) as f32;
+        for child in children {
+            if let Ok((_, mut layer_projection)) = cameras.get_mut(*child) {
+                if projection.is_changed() {
+                    *layer_projection = projection.clone();
+                }
+            }
+            if let Ok((quad, material, mut transform)) = quads.get_mut(*child) {
+                transform.scale = area.extend(1.0);
+                let blur = dof.blur(dof.bucket_depth(quad.bucket));
+                let radius = Vec2::new(blur * aspect, blur);
+                if materials.get(material).is_some_and(|m| m.radius != radius) {
+                    materials.get_mut(material).unwrap().radius = radius;
+                }
+            }
+        }
+    }
+}
+
+impl Plugin for MatrixDofPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(Material2dPlugin::<DofLayerMaterial>::default())
+            .register_type::<MatrixDof>()
+            .add_systems(Update, setup_dof_layers)
+            .add_systems(Update, assign_dof_layers)
+            .add_systems(Update, sync_dof_layers)
+            .add_systems(Update, component_animator_system::<MatrixDof>);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_unusable_layers() {
+        assert!(MatrixDof::default().check().is_ok());
+        for dof in [
+            MatrixDof {
+                layers: 0,
+                ..default()
+            },
+            MatrixDof {
+                first_layer: 0,
+                ..default()
+            },
+            MatrixDof {
+                first_layer: 1,
+                ..default()
+            },
+        ] {
+            assert!(dof.check().is_err());
+            // Doesn't panic either way.
+            dof.bucket(0.0);
+            dof.composite_layer();
+        }
+    }
+
+    #[test]
+    fn buckets_cover_the_depth_range() {
+        let dof = MatrixDof::default();
+        assert_eq!(dof.bucket(dof.far), 0);
+        assert_eq!(dof.bucket(dof.near), dof.layers - 1);
+        assert_eq!(dof.bucket(dof.near + 10.0), dof.layers - 1);
+        for bucket in 0..dof.layers {
+            assert_eq!(dof.bucket(dof.bucket_depth(bucket)), bucket);
+        }
+    }
+}
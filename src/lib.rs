@@ -0,0 +1,23 @@
+#![allow(clippy::type_complexity)]
+
+pub mod dof;
+pub mod matrix_audio;
+pub mod matrix_bench;
+pub mod matrix_budget;
+pub mod matrix_control;
+pub mod matrix_field;
+pub mod matrix_fog;
+pub mod matrix_glitch;
+pub mod matrix_glyphs;
+pub mod matrix_grading;
+pub mod matrix_letter;
+pub mod matrix_panel;
+pub mod matrix_pointer;
+pub mod matrix_preset;
+pub mod matrix_shader_rain;
+pub mod matrix_sound;
+pub mod matrix_state;
+pub mod matrix_strip;
+pub mod matrix_texture;
+pub mod post;
+mod utils;
@@ -1,34 +1,21 @@
-mod matrix_field;
-mod matrix_letter;
-mod matrix_strip;
-//mod post;
-mod utils;
-
-use matrix_field::*;
-use matrix_letter::*;
-use matrix_strip::*;
-//use post::*;
+use bevy_matrix::{
+    dof::*, matrix_audio::*, matrix_budget::*, matrix_control::*, matrix_field::*, matrix_fog::*,
+    matrix_glitch::*, matrix_grading::*, matrix_letter::*, matrix_panel::*, matrix_pointer::*,
+    matrix_preset::BloomPreset, matrix_shader_rain::*, matrix_sound::*, matrix_strip::*,
+    matrix_texture::*, post::*,
+};
 
 use bevy::{
-    core_pipeline::bloom::{self, BloomPrefilterSettings, BloomSettings},
     diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
     prelude::*,
-    render::{
-        camera::{RenderTarget, ScalingMode},
-        render_resource::{
-            Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
-        },
-        view::RenderLayers,
-    },
-    sprite::{Material2dPlugin, MaterialMesh2dBundle},
-    window::{PresentMode, PrimaryWindow, WindowMode, WindowResized},
+    render::camera::ScalingMode,
+    window::{PresentMode, WindowMode},
 };
-use bevy_editor_pls::prelude::*;
 use bevy_tweening::*;
 
 fn main() {
-    App::new()
-        .insert_resource(ClearColor(Color::BLACK))
+    let mut app = App::new();
+    app.insert_resource(ClearColor(Color::BLACK))
         .add_plugins(DefaultPlugins.set(WindowPlugin {
             primary_window: Some(Window {
                 title: "Matrix".to_string(),
@@ -40,160 +27,127 @@ fn main() {
         }))
         .add_plugins(TweeningPlugin)
         .add_plugins(LogDiagnosticsPlugin::default())
-        .add_plugins(FrameTimeDiagnosticsPlugin::default())
+        .add_plugins(FrameTimeDiagnosticsPlugin)
+        .add_plugins(MatrixBudgetPlugin)
         .add_plugins(MatrixLetterPlugin)
         .add_plugins(MatrixStripPlugin)
         .add_plugins(MatrixFieldPlugin)
-        //.add_plugin(PostPlugin)
-        .add_systems(Update, close_on_esc)
-        .add_systems(Startup, setup)
+        .add_plugins(MatrixShaderRainPlugin)
+        .add_plugins(MatrixFogPlugin)
+        .add_plugins(MatrixTexturePlugin)
+        .add_plugins(MatrixPointerPlugin)
+        .add_plugins(MatrixAudioPlugin)
+        .add_plugins(MatrixPanelPlugin)
+        .add_plugins(MatrixDofPlugin)
+        .add_plugins(MatrixGradingPlugin)
+        //.add_plugins(MatrixSoundPlugin::default())
+        .add_plugins(MatrixPostPlugin)
+        .add_plugins(MatrixGlitchPlugin)
         //.add_plugin(WorldInspectorPlugin::default())
         //.add_plugins(EditorPlugin::default())
-        .add_systems(Update, update_bloom_settings)
-        .run();
+        .add_systems(Update, close_on_esc)
+        .add_systems(Startup, setup);
+    if let Some(control) = control_plugin() {
+        app.add_plugins(control)
+            .add_systems(Startup, setup_messages)
+            .add_systems(Update, show_messages);
+    }
+    app.run();
 }
 
-fn setup(
-    mut commands: Commands,
-    mut images: ResMut<Assets<Image>>,
-    primary_window: Query<&Window, With<PrimaryWindow>>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    //mut post_materials: ResMut<Assets<PostProcessingMaterial>>,
+/// The control server only runs when asked for: `--control` listens on the
+/// default address, `--control=ADDR` on another one, and `--osc=ADDR` also
+/// listens for OSC.
+fn control_plugin() -> Option<MatrixControlPlugin> {
+    let mut control = None;
+    let mut osc_addr = None;
+    for arg in std::env::args().skip(1) {
+        if arg == "--control" {
+            control = Some(MatrixControlPlugin::default());
+        } else if let Some(addr) = arg.strip_prefix("--control=") {
+            control = Some(MatrixControlPlugin {
+                addr: addr.to_string(),
+                ..default()
+            });
+        } else if let Some(addr) = arg.strip_prefix("--osc=") {
+            osc_addr = Some(addr.to_string());
+        }
+    }
+    if osc_addr.is_some() {
+        control
+            .get_or_insert_with(MatrixControlPlugin::default)
+            .osc_addr = osc_addr;
+    }
+    control
+}
+
+/// Shows the last message sent by a controller for a few seconds.
+#[derive(Component)]
+struct MessageText(Timer);
+
+fn setup_messages(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 48.0,
+                color: Color::srgb(0.6, 1.0, 0.7),
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Percent(10.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        })
+        .with_text_justify(JustifyText::Center),
+        MessageText(Timer::from_seconds(5.0, TimerMode::Once)),
+    ));
+}
+
+fn show_messages(
+    mut messages: EventReader<MatrixMessage>,
+    mut texts: Query<(&mut Text, &mut MessageText)>,
+    time: Res<Time>,
 ) {
-    let window = primary_window.single();
+    let latest = messages.read().last();
+    for (mut text, mut message) in &mut texts {
+        if let Some(latest) = latest {
+            text.sections[0].value.clone_from(&latest.text);
+            message.0.reset();
+        } else if message.0.tick(time.delta()).just_finished() {
+            text.sections[0].value.clear();
+        }
+    }
+}
 
-    let size = Extent3d {
-        width: 3840,
-        height: 2400,
-        ..default()
-    };
-    let mut image = Image {
-        texture_descriptor: TextureDescriptor {
-            label: None,
-            size,
-            dimension: TextureDimension::D2,
-            format: TextureFormat::Bgra8UnormSrgb,
-            mip_level_count: 1,
-            sample_count: 1,
-            usage: TextureUsages::TEXTURE_BINDING
-                | TextureUsages::COPY_DST
-                | TextureUsages::RENDER_ATTACHMENT,
-            view_formats: &[TextureFormat::Bgra8UnormSrgb],
-        },
-        ..default()
-    };
-    image.resize(size);
-    //let image_handle = images.add(image);
+fn setup(mut commands: Commands) {
     // Camera
     let mut cam = Camera2dBundle {
         transform: Transform::from_translation(Vec3::new(0.0, 0.0, 100.0)),
         camera: Camera {
             hdr: true,
-            //target: RenderTarget::Image(image_handle.clone()),
             ..default()
         },
         ..default()
     };
-    //commands.insert_resource(PostImage(image_handle.clone()));
     cam.projection.scaling_mode = ScalingMode::FixedVertical(16.0);
     commands.spawn((
         cam,
-        BloomSettings {
-            prefilter_settings: BloomPrefilterSettings {
-                threshold_softness: -2.9161227,
-                threshold: -3.9751358,
-            },
-            intensity: 0.8723975,
-            ..Default::default()
-        },
-    ));
-
-    /*let post_layer = RenderLayers::layer((RenderLayers::TOTAL_LAYERS - 1) as u8);
-    let quad_handle = meshes.add(Mesh::from(shape::Quad::new(Vec2::new(
-        1.0 as f32, 1.0 as f32,
-    ))));
-
-    let material_handle = post_materials.add(PostProcessingMaterial {
-        source_image: image_handle.clone(),
-        time: ShaderTime {
-            secs_since_startup: 0.0,
-            dt: 0.0,
-        },
-    });
-
-    commands.spawn((
-        MaterialMesh2dBundle {
-            mesh: quad_handle.into(),
-            material: material_handle,
-            transform: Transform {
-                translation: Vec3::new(0.0, 0.0, 1.5),
-                scale: Vec3::new(size.width as f32, size.height as f32, 1.0),
-                ..default()
-            },
+        BloomPreset::default().settings(),
+        MatrixGrading::default(),
+        MatrixPost {
+            enabled: false,
             ..default()
         },
-        post_layer,
-        PostQuad,
+        MatrixDof::default(),
+        MatrixPointerCamera,
+        MatrixSoundCamera,
     ));
 
-    commands.spawn((
-        Camera2dBundle {
-            camera: Camera {
-                priority: 1,
-                ..default()
-            },
-            ..default()
-        },
-        post_layer,
-    ));*/
-}
-
-fn resize(resize_event: Res<Events<WindowResized>>) {
-    let mut reader = resize_event.get_reader();
-    for e in reader.read(&resize_event) {
-        let mut size = Extent3d {
-            width: e.width as u32,
-            height: e.height as u32,
-            ..default()
-        };
-    }
-}
-
-fn update_bloom_settings(
-    mut camera: Query<&mut BloomSettings>,
-    keycode: Res<ButtonInput<KeyCode>>,
-    time: Res<Time>,
-) {
-    let mut bloom_settings = camera.single_mut();
-
-    let dt = time.delta_seconds();
-
-    if keycode.pressed(KeyCode::KeyQ) {
-        bloom_settings.prefilter_settings.threshold -= dt;
-    }
-    if keycode.pressed(KeyCode::KeyW) {
-        bloom_settings.prefilter_settings.threshold += dt;
-    }
-
-    if keycode.pressed(KeyCode::KeyR) {
-        bloom_settings.prefilter_settings.threshold_softness -= dt;
-    }
-    if keycode.pressed(KeyCode::KeyT) {
-        bloom_settings.prefilter_settings.threshold_softness += dt;
-    }
-
-    if keycode.pressed(KeyCode::KeyD) {
-        bloom_settings.intensity -= dt;
-    }
-    if keycode.pressed(KeyCode::KeyF) {
-        bloom_settings.intensity += dt;
-    }
-    println!(
-        "I: {}, K: {}, T: {}",
-        bloom_settings.intensity,
-        bloom_settings.prefilter_settings.threshold_softness,
-        bloom_settings.prefilter_settings.threshold
-    );
+    commands.spawn(MatrixFieldBundle::default());
 }
 
 pub fn close_on_esc(
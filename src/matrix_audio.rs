@@ -0,0 +1,441 @@
+use std::{f32::consts::TAU, sync::Arc};
+
+use bevy::{
+    audio::{Decodable, Source},
+    core_pipeline::bloom::BloomSettings,
+    prelude::*,
+    tasks::{block_on, poll_once, AsyncComputeTaskPool, Task},
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Reflect)]
+pub enum AudioBand {
+    Bass,
+    Mid,
+    High,
+    /// Pulse that jumps to 1.0 on a beat and decays afterwards.
+    Beat,
+}
+
+/// Streaming band energy and beat detection on mono PCM.
+///
+/// The signal is split with two one-pole low-pass filters at `bass_cutoff`
+/// and `mid_cutoff`. A beat is a frame whose bass energy exceeds its running
+/// average by `beat_threshold`.
+#[derive(Clone, Reflect)]
+#[reflect(Default)]
+pub struct AudioAnalyzer {
+    pub bass_cutoff: f32,
+    pub mid_cutoff: f32,
+    pub beat_threshold: f32,
+    /// Shortest time between two beats, in seconds.
+    pub beat_interval: f32,
+    /// Decay rate of the beat pulse, per second.
+    pub beat_decay: f32,
+    /// Time constant of the band smoothing, in seconds.
+    pub smoothing: f32,
+    bass: f32,
+    mid: f32,
+    high: f32,
+    beat: f32,
+    beats: u32,
+    bass_average: f32,
+    since_beat: f32,
+    low_state: f32,
+    mid_state: f32,
+}
+
+impl Default for AudioAnalyzer {
+    fn default() -> Self {
+        Self {
+            bass_cutoff: 250.0,
+            mid_cutoff: 2000.0,
+            beat_threshold: 1.5,
+            beat_interval: 0.25,
+            beat_decay: 6.0,
+            smoothing: 0.05,
+            bass: 0.0,
+            mid: 0.0,
+            high: 0.0,
+            beat: 0.0,
+            beats: 0,
+            bass_average: 0.0,
+            since_beat: f32::INFINITY,
+            low_state: 0.0,
+            mid_state: 0.0,
+        }
+    }
+}
+
+fn one_pole(cutoff: f32, sample_rate: u32) -> f32 {
+    1.0 - (-TAU * cutoff / sample_rate as f32).exp()
+}
+
+fn approach(value: f32, target: f32, dt: f32, time_constant: f32) -> f32 {
+    if time_constant <= 0.0 {
+        return target;
+    }
+    value + (target - value) * (1.0 - (-dt / time_constant).exp())
+}
+
+impl AudioAnalyzer {
+    /// Analyzes the `samples` that played during the last `dt` seconds.
+    pub fn analyze(&mut self, samples: &[f32], sample_rate: u32, dt: f32) {
+        let (mut bass, mut mid, mut high) = (0.0, 0.0, 0.0);
+        if !samples.is_empty() && sample_rate > 0 {
+            let low_k = one_pole(self.bass_cutoff, sample_rate);
+            let mid_k = one_pole(self.mid_cutoff, sample_rate);
+            for &x in samples {
+                self.low_state += (x - self.low_state) * low_k;
+                self.mid_state += (x - self.mid_state) * mid_k;
+                bass += self.low_state * self.low_state;
+                mid += (self.mid_state - self.low_state).powi(2);
+                high += (x - self.mid_state).powi(2);
+            }
+            let n = samples.len() as f32;
+            (bass, mid, high) = ((bass / n).sqrt(), (mid / n).sqrt(), (high / n).sqrt());
+        }
+
+        self.bass = approach(self.bass, bass, dt, self.smoothing);
+        self.mid = approach(self.mid, mid, dt, self.smoothing);
+        self.high = approach(self.high, high, dt, self.smoothing);
+
+        self.since_beat += dt;
+        self.beat *= (-self.beat_decay * dt).exp();
+        if bass > self.bass_average * self.beat_threshold
+            && bass > 0.01
+            && self.since_beat >= self.beat_interval
+        {
+            self.beat = 1.0;
+            self.beats += 1;
+            self.since_beat = 0.0;
+        }
+        self.bass_average = approach(self.bass_average, bass, dt, 1.0);
+    }
+
+    pub fn level(&self, band: AudioBand) -> f32 {
+        match band {
+            AudioBand::Bass => self.bass,
+            AudioBand::Mid => self.mid,
+            AudioBand::High => self.high,
+            AudioBand::Beat => self.beat,
+        }
+    }
+
+    /// Number of beats detected so far.
+    pub fn beats(&self) -> u32 {
+        self.beats
+    }
+}
+
+/// How a band drives a parameter: the parameter moves by `gain * level`.
+#[derive(Clone, Copy, Reflect)]
+pub struct AudioDrive {
+    pub band: AudioBand,
+    pub gain: f32,
+}
+
+impl AudioDrive {
+    pub fn new(band: AudioBand, gain: f32) -> Self {
+        Self { band, gain }
+    }
+}
+
+/// Which band drives which rain parameter. `None` leaves it alone.
+#[derive(Resource, Reflect, Clone)]
+#[reflect(Resource, Default)]
+pub struct MatrixAudioMapping {
+    /// Added to the field spawn rate multiplier.
+    pub spawn_rate: Option<AudioDrive>,
+    /// Added to the glyph flip rate multiplier.
+    pub flip_rate: Option<AudioDrive>,
+    /// Added to the intensity of [`MatrixAudioBloom`] cameras.
+    pub bloom: Option<AudioDrive>,
+    /// Palette hue rotation of new strips, in degrees.
+    pub hue_shift: Option<AudioDrive>,
+}
+
+impl Default for MatrixAudioMapping {
+    fn default() -> Self {
+        Self {
+            spawn_rate: Some(AudioDrive::new(AudioBand::Bass, 8.0)),
+            flip_rate: Some(AudioDrive::new(AudioBand::High, 20.0)),
+            bloom: Some(AudioDrive::new(AudioBand::Beat, 0.5)),
+            hue_shift: Some(AudioDrive::new(AudioBand::Mid, 120.0)),
+        }
+    }
+}
+
+/// Current effect of the audio on the rain, as computed from the
+/// [`MatrixAudioMapping`].
+#[derive(Clone, Copy, Reflect)]
+#[reflect(Default)]
+pub struct AudioModulation {
+    pub spawn_rate: f32,
+    pub flip_rate: f32,
+    pub bloom: f32,
+    pub hue_shift: f32,
+}
+
+impl Default for AudioModulation {
+    fn default() -> Self {
+        Self {
+            spawn_rate: 1.0,
+            flip_rate: 1.0,
+            bloom: 0.0,
+            hue_shift: 0.0,
+        }
+    }
+}
+
+/// Audio analysis state. Samples either come from a [`MatrixAudioTrack`] or
+/// are pushed by the host app with [`MatrixAudio::push_samples`].
+#[derive(Resource, Reflect, Default)]
+#[reflect(Resource, Default)]
+pub struct MatrixAudio {
+    pub analyzer: AudioAnalyzer,
+    modulation: AudioModulation,
+    #[reflect(ignore)]
+    pending: Vec<f32>,
+    sample_rate: u32,
+}
+
+impl MatrixAudio {
+    /// Queues mono samples for analysis in the next frame.
+    pub fn push_samples(&mut self, samples: &[f32], sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        self.pending.extend_from_slice(samples);
+    }
+
+    /// Queues interleaved samples, which are mixed down to mono.
+    pub fn push_interleaved(&mut self, samples: &[f32], channels: usize, sample_rate: u32) {
+        let channels = channels.max(1);
+        self.sample_rate = sample_rate;
+        self.pending.extend(
+            samples
+                .chunks(channels)
+                .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32),
+        );
+    }
+
+    pub fn level(&self, band: AudioBand) -> f32 {
+        self.analyzer.level(band)
+    }
+
+    pub fn modulation(&self) -> AudioModulation {
+        self.modulation
+    }
+
+    fn drive(&self, drive: Option<AudioDrive>) -> f32 {
+        drive.map_or(0.0, |d| d.gain * self.level(d.band))
+    }
+}
+
+/// Feeds the analyzer from an audio asset, in step with time.
+///
+/// Playback is left to the app, e.g. an `AudioBundle` with the same source
+/// on the same entity, so the analysis also runs without an audio device.
+/// The asset is decoded in the background once it is loaded, and the track
+/// starts feeding when that is done.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct MatrixAudioTrack {
+    pub source: Handle<AudioSource>,
+    pub looping: bool,
+    #[reflect(ignore)]
+    samples: Option<Arc<[f32]>>,
+    #[reflect(ignore)]
+    decoding: Option<Task<(Arc<[f32]>, u32)>>,
+    sample_rate: u32,
+    position: f64,
+}
+
+impl MatrixAudioTrack {
+    pub fn new(source: Handle<AudioSource>) -> Self {
+        Self {
+            source,
+            looping: false,
+            samples: None,
+            decoding: None,
+            sample_rate: 0,
+            position: 0.0,
+        }
+    }
+
+    pub fn with_looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    pub fn finished(&self) -> bool {
+        self.samples
+            .as_ref()
+            .is_some_and(|s| !self.looping && self.position as usize >= s.len())
+    }
+}
+
+/// Adds the audio bloom modulation on top of `intensity` on this camera.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct MatrixAudioBloom {
+    pub intensity: f32,
+}
+
+pub struct MatrixAudioPlugin;
+
+fn decode(source: &AudioSource) -> (Arc<[f32]>, u32) {
+    let decoder = source.decoder();
+    let channels = decoder.channels().max(1) as usize;
+    let sample_rate = decoder.sample_rate();
+    let interleaved: Vec<f32> = decoder.map(|s| s as f32 / i16::MAX as f32).collect();
+    let mono = interleaved
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+        .collect();
+    (mono, sample_rate)
+}
+
+fn feed_tracks(
+    mut tracks: Query<&mut MatrixAudioTrack>,
+    sources: Option<Res<Assets<AudioSource>>>,
+    mut audio: ResMut<MatrixAudio>,
+    time: Res<Time>,
+) {
+    for mut track in &mut tracks {
+        if track.samples.is_none() {
+            if track.decoding.is_none() {
+                let Some(source) = sources.as_ref().and_then(|s| s.get(&track.source)) else {
+                    continue;
+                };
+                let source = source.clone();
+                track.decoding =
+                    Some(AsyncComputeTaskPool::get().spawn(async move { decode(&source) }));
+            }
+            let Some((samples, sample_rate)) = track
+                .decoding
+                .as_mut()
+                .and_then(|task| block_on(poll_once(task)))
+            else {
+                continue;
+            };
+            track.decoding = None;
+            track.samples = Some(samples);
+            track.sample_rate = sample_rate;
+        }
+        let Some(samples) = track.samples.clone() else {
+            continue;
+        };
+        if samples.is_empty() {
+            continue;
+        }
+
+        let start = track.position;
+        let end = start + time.delta_seconds_f64() * track.sample_rate as f64;
+        track.position = end;
+        let (start, end) = (start as usize, end as usize);
+        if track.looping {
+            let len = samples.len();
+            let chunk: Vec<f32> = (start..end).map(|i| samples[i % len]).collect();
+            audio.push_samples(&chunk, track.sample_rate);
+            track.position %= len as f64;
+        } else if start < samples.len() {
+            audio.push_samples(&samples[start..end.min(samples.len())], track.sample_rate);
+        }
+    }
+}
+
+fn analyze_audio(
+    mut audio: ResMut<MatrixAudio>,
+    mapping: Res<MatrixAudioMapping>,
+    time: Res<Time>,
+) {
+    let audio = &mut *audio;
+    audio
+        .analyzer
+        .analyze(&audio.pending, audio.sample_rate, time.delta_seconds());
+    audio.pending.clear();
+    audio.modulation = AudioModulation {
+        spawn_rate: 1.0 + audio.drive(mapping.spawn_rate),
+        flip_rate: 1.0 + audio.drive(mapping.flip_rate),
+        bloom: audio.drive(mapping.bloom),
+        hue_shift: audio.drive(mapping.hue_shift),
+    };
+}
+
+fn audio_bloom(
+    mut cameras: Query<(&mut BloomSettings, &MatrixAudioBloom)>,
+    audio: Res<MatrixAudio>,
+) {
+    for (mut bloom, audio_bloom) in &mut cameras {
+        bloom.intensity = audio_bloom.intensity + audio.modulation().bloom;
+    }
+}
+
+impl Plugin for MatrixAudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MatrixAudio>()
+            .init_resource::<MatrixAudioMapping>()
+            .register_type::<MatrixAudio>()
+            .register_type::<MatrixAudioMapping>()
+            .register_type::<MatrixAudioTrack>()
+            .register_type::<MatrixAudioBloom>()
+            .add_systems(PreUpdate, (feed_tracks, analyze_audio).chain())
+            .add_systems(Update, audio_bloom);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+
+    #[test]
+    fn wav_track_drives_modulation() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default(), MatrixAudioPlugin))
+            .init_asset::<AudioSource>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+                1.0 / 60.0,
+            )));
+        // Two seconds of a kick every half second and hi-hats between them.
+        let source = app
+            .world_mut()
+            .resource_mut::<Assets<AudioSource>>()
+            .add(AudioSource {
+                bytes: include_bytes!("../assets/sounds/beat.wav")
+                    .as_slice()
+                    .into(),
+            });
+        let track = app.world_mut().spawn(MatrixAudioTrack::new(source)).id();
+
+        let mut peak = AudioModulation::default();
+        for _ in 0..10_000 {
+            app.update();
+            let modulation = app.world().resource::<MatrixAudio>().modulation();
+            peak.spawn_rate = peak.spawn_rate.max(modulation.spawn_rate);
+            peak.flip_rate = peak.flip_rate.max(modulation.flip_rate);
+            peak.bloom = peak.bloom.max(modulation.bloom);
+            if app
+                .world()
+                .get::<MatrixAudioTrack>(track)
+                .unwrap()
+                .finished()
+            {
+                break;
+            }
+        }
+
+        let audio = app.world().resource::<MatrixAudio>();
+        assert!(
+            (3..=4).contains(&audio.analyzer.beats()),
+            "{}",
+            audio.analyzer.beats()
+        );
+        assert!(peak.spawn_rate > 1.5, "{}", peak.spawn_rate);
+        assert!(peak.flip_rate > 1.1, "{}", peak.flip_rate);
+        // The beat pulse drives the bloom with a gain of 0.5.
+        assert!(peak.bloom > 0.4, "{}", peak.bloom);
+    }
+}
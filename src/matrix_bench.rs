@@ -0,0 +1,295 @@
+use std::time::{Duration, Instant};
+
+use bevy::{
+    diagnostic::{
+        Diagnostic, DiagnosticPath, Diagnostics, DiagnosticsStore, EntityCountDiagnosticsPlugin,
+        FrameTimeDiagnosticsPlugin, RegisterDiagnostic,
+    },
+    ecs::schedule::{ExecutorKind, ScheduleLabel},
+    prelude::*,
+    time::TimeUpdateStrategy,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    matrix_field::{MatrixField, MatrixFieldPlugin},
+    matrix_letter::{LetterPhase, MatrixLetterPlugin},
+    matrix_strip::{MatrixStrip, MatrixStripPlugin},
+    utils::{seed_rng, SystemTimings},
+};
+
+/// Systems whose run time is reported, in milliseconds per frame, under
+/// `matrix/<name>`.
+pub const TIMED_SYSTEMS: [&str; 7] = [
+    "spawn_strips",
+    "spawn_letters",
+    "letter_requests",
+    "change_text",
+    "letter_death",
+    "update_color",
+    "animators",
+];
+
+pub const LIVE_LETTERS: DiagnosticPath = DiagnosticPath::const_new("matrix/letters");
+pub const LIVE_STRIPS: DiagnosticPath = DiagnosticPath::const_new("matrix/strips");
+
+pub fn system_path(name: &str) -> DiagnosticPath {
+    DiagnosticPath::from_components(["matrix", name])
+}
+
+/// Records the run time of the [`TIMED_SYSTEMS`] and the number of live
+/// letters and strips as diagnostics, so `LogDiagnosticsPlugin` prints
+/// them too.
+///
+/// With a `seed`, every schedule runs single-threaded and the rain's random
+/// numbers are seeded, so runs with the same settings are identical.
+#[derive(Default)]
+pub struct MatrixBenchPlugin {
+    pub seed: Option<u64>,
+}
+
+fn record_diagnostics(
+    mut diagnostics: Diagnostics,
+    letters: Query<&LetterPhase>,
+    strips: Query<&MatrixStrip>,
+    timings: Res<SystemTimings>,
+) {
+    let timings = timings.take();
+    for name in TIMED_SYSTEMS {
+        let time = timings
+            .iter()
+            .find(|(timed, _)| *timed == name)
+            .map_or(Duration::ZERO, |(_, time)| *time);
+        diagnostics.add_measurement(&system_path(name), || time.as_secs_f64() * 1000.0);
+    }
+    diagnostics.add_measurement(&LIVE_LETTERS, || {
+        letters
+            .iter()
+            .filter(|phase| **phase != LetterPhase::Pooled)
+            .count() as f64
+    });
+    diagnostics.add_measurement(&LIVE_STRIPS, || {
+        strips.iter().filter(|strip| !strip.is_pooled()).count() as f64
+    });
+}
+
+impl Plugin for MatrixBenchPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SystemTimings>();
+        if let Some(seed) = self.seed {
+            seed_rng(seed);
+            for label in [
+                Startup.intern(),
+                First.intern(),
+                PreUpdate.intern(),
+                Update.intern(),
+                PostUpdate.intern(),
+                Last.intern(),
+            ] {
+                app.edit_schedule(label, |schedule| {
+                    schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+                });
+            }
+        }
+        for name in TIMED_SYSTEMS {
+            app.register_diagnostic(Diagnostic::new(system_path(name)).with_suffix("ms"));
+        }
+        app.register_diagnostic(Diagnostic::new(LIVE_LETTERS))
+            .register_diagnostic(Diagnostic::new(LIVE_STRIPS))
+            .add_systems(Last, record_diagnostics);
+    }
+}
+
+/// What to run: one headless rain per density, for `seconds` of simulated
+/// time at `fps`.
+#[derive(Clone, Debug)]
+pub struct BenchSettings {
+    /// Strips spawned per second.
+    pub densities: Vec<f32>,
+    pub seconds: f32,
+    pub fps: f32,
+    pub seed: u64,
+}
+
+impl Default for BenchSettings {
+    fn default() -> Self {
+        Self {
+            densities: vec![20.0, 50.0, 100.0],
+            seconds: 30.0,
+            fps: 60.0,
+            seed: 0,
+        }
+    }
+}
+
+/// Milliseconds, or a count for entity statistics.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct Stats {
+    pub mean: f64,
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl Stats {
+    pub fn new(mut values: Vec<f64>) -> Self {
+        if values.is_empty() {
+            return Self::default();
+        }
+        values.sort_by(f64::total_cmp);
+        let percentile = |p: f64| values[((values.len() - 1) as f64 * p).round() as usize];
+        Self {
+            mean: values.iter().sum::<f64>() / values.len() as f64,
+            p50: percentile(0.5),
+            p95: percentile(0.95),
+            p99: percentile(0.99),
+            max: values[values.len() - 1],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SystemTiming {
+    pub name: String,
+    pub time: Stats,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BenchRun {
+    pub density: f32,
+    pub frames: usize,
+    /// Wall time of a whole frame. Simulated time always advances by
+    /// `1 / fps`.
+    pub frame_time: Stats,
+    pub systems: Vec<SystemTiming>,
+    pub letters: Stats,
+    pub strips: Stats,
+    pub entities: Stats,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BenchReport {
+    pub seed: u64,
+    pub seconds: f32,
+    pub fps: f32,
+    pub runs: Vec<BenchRun>,
+}
+
+impl BenchReport {
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| e.to_string())
+    }
+
+    /// One row per run, statistics flattened into `<name>_<stat>` columns.
+    pub fn to_csv(&self) -> String {
+        let series = |run: &BenchRun| {
+            let mut series = vec![("frame_time".to_string(), run.frame_time)];
+            series.extend(run.systems.iter().map(|s| (s.name.clone(), s.time)));
+            series.push(("letters".to_string(), run.letters));
+            series.push(("strips".to_string(), run.strips));
+            series.push(("entities".to_string(), run.entities));
+            series
+        };
+        let stats = |s: Stats| [s.mean, s.p50, s.p95, s.p99, s.max];
+
+        let mut csv = String::from("density,frames");
+        if let Some(run) = self.runs.first() {
+            for (name, _) in series(run) {
+                for stat in ["mean", "p50", "p95", "p99", "max"] {
+                    csv += &format!(",{name}_{stat}");
+                }
+            }
+        }
+        csv.push('\n');
+        for run in &self.runs {
+            csv += &format!("{},{}", run.density, run.frames);
+            for (_, s) in series(run) {
+                for value in stats(s) {
+                    csv += &format!(",{value:.4}");
+                }
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+fn bench_app(density: f32, settings: &BenchSettings) -> App {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        AssetPlugin::default(),
+        TransformPlugin,
+        HierarchyPlugin,
+    ))
+    .init_asset::<Font>()
+    .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+        1.0 / settings.fps,
+    )))
+    .add_plugins((FrameTimeDiagnosticsPlugin, EntityCountDiagnosticsPlugin))
+    .add_plugins((MatrixLetterPlugin, MatrixStripPlugin, MatrixFieldPlugin))
+    .add_plugins(MatrixBenchPlugin {
+        seed: Some(settings.seed),
+    });
+    app.world_mut().spawn((
+        SpatialBundle::default(),
+        MatrixField {
+            spawn_interval: 1.0 / density.max(f32::EPSILON),
+            ..Default::default()
+        },
+    ));
+    app
+}
+
+/// Runs the rain headless at every density and collects the timings.
+pub fn run_bench(settings: &BenchSettings) -> BenchReport {
+    let frames = (settings.seconds * settings.fps).ceil() as usize;
+    let runs = settings
+        .densities
+        .iter()
+        .map(|&density| {
+            let mut app = bench_app(density, settings);
+            let mut frame_times = Vec::with_capacity(frames);
+            let mut systems = vec![Vec::with_capacity(frames); TIMED_SYSTEMS.len()];
+            let (mut letters, mut strips, mut entities) = (Vec::new(), Vec::new(), Vec::new());
+            for _ in 0..frames {
+                let start = Instant::now();
+                app.update();
+                frame_times.push(start.elapsed().as_secs_f64() * 1000.0);
+
+                let store = app.world().resource::<DiagnosticsStore>();
+                let value =
+                    |path: &DiagnosticPath| store.get_measurement(path).map_or(0.0, |m| m.value);
+                for (name, times) in TIMED_SYSTEMS.iter().zip(&mut systems) {
+                    times.push(value(&system_path(name)));
+                }
+                letters.push(value(&LIVE_LETTERS));
+                strips.push(value(&LIVE_STRIPS));
+                entities.push(value(&EntityCountDiagnosticsPlugin::ENTITY_COUNT));
+            }
+            BenchRun {
+                density,
+                frames,
+                frame_time: Stats::new(frame_times),
+                systems: TIMED_SYSTEMS
+                    .iter()
+                    .zip(systems)
+                    .map(|(name, times)| SystemTiming {
+                        name: name.to_string(),
+                        time: Stats::new(times),
+                    })
+                    .collect(),
+                letters: Stats::new(letters),
+                strips: Stats::new(strips),
+                entities: Stats::new(entities),
+            }
+        })
+        .collect();
+    BenchReport {
+        seed: settings.seed,
+        seconds: settings.seconds,
+        fps: settings.fps,
+        runs,
+    }
+}
@@ -0,0 +1,136 @@
+use bevy::{
+    diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin},
+    prelude::*,
+};
+
+use crate::{matrix_letter::LetterPhase, matrix_strip::MatrixStrip};
+
+/// Caps how much rain is alive at once and trades detail for frame rate.
+///
+/// While the live letters or strips exceed their caps, or frames take
+/// longer than `target_frame_time`, the budget's quality drops towards
+/// `min_quality`. Fields then spawn fewer and shorter strips and letters
+/// flip less often. Once everything is back below `headroom` times its
+/// limit, quality recovers.
+#[derive(Resource, Reflect, Clone)]
+#[reflect(Resource, Default)]
+pub struct RainBudget {
+    pub max_letters: usize,
+    pub max_strips: usize,
+    /// Frame time in seconds the rain should stay under.
+    pub target_frame_time: f32,
+    /// Fraction of every limit below which quality recovers.
+    pub headroom: f32,
+    pub min_quality: f32,
+    /// Quality lost per second while over budget.
+    pub degrade_rate: f32,
+    /// Quality regained per second while under budget.
+    pub recover_rate: f32,
+    quality: f32,
+    letters: usize,
+    strips: usize,
+    frame_time: Option<f32>,
+}
+
+impl Default for RainBudget {
+    fn default() -> Self {
+        Self {
+            max_letters: 8000,
+            max_strips: 400,
+            target_frame_time: 1.0 / 50.0,
+            headroom: 0.8,
+            min_quality: 0.2,
+            degrade_rate: 0.5,
+            recover_rate: 0.1,
+            quality: 1.0,
+            letters: 0,
+            strips: 0,
+            frame_time: None,
+        }
+    }
+}
+
+impl RainBudget {
+    /// Between `min_quality` and 1.0. Scales the strip spawn rate, strip
+    /// length and glyph flip rate.
+    pub fn quality(&self) -> f32 {
+        self.quality
+    }
+
+    pub fn letters(&self) -> usize {
+        self.letters
+    }
+
+    pub fn strips(&self) -> usize {
+        self.strips
+    }
+
+    /// Smoothed frame time in seconds, if frame time diagnostics are on.
+    pub fn frame_time(&self) -> Option<f32> {
+        self.frame_time
+    }
+
+    /// No new strips are spawned while the caps are reached.
+    pub fn is_full(&self) -> bool {
+        self.letters >= self.max_letters || self.strips >= self.max_strips
+    }
+
+    /// Scales a strip length by the quality, keeping at least one letter.
+    pub fn max_length(&self, max_length: u32) -> u32 {
+        ((max_length as f32 * self.quality).round() as u32).max(1)
+    }
+
+    fn is_over(&self) -> bool {
+        self.letters > self.max_letters
+            || self.strips > self.max_strips
+            || self.frame_time.is_some_and(|t| t > self.target_frame_time)
+    }
+
+    fn has_headroom(&self) -> bool {
+        let below = |value: f32, limit: f32| value < limit * self.headroom;
+        below(self.letters as f32, self.max_letters as f32)
+            && below(self.strips as f32, self.max_strips as f32)
+            && self
+                .frame_time
+                .map_or(true, |t| below(t, self.target_frame_time))
+    }
+}
+
+pub struct MatrixBudgetPlugin;
+
+fn update_budget(
+    mut budget: ResMut<RainBudget>,
+    letters: Query<&LetterPhase>,
+    strips: Query<&MatrixStrip>,
+    diagnostics: Option<Res<DiagnosticsStore>>,
+    time: Res<Time>,
+) {
+    let budget = &mut *budget;
+    // Pooled entities are hidden and cost next to nothing.
+    budget.letters = letters
+        .iter()
+        .filter(|phase| **phase != LetterPhase::Pooled)
+        .count();
+    budget.strips = strips.iter().filter(|strip| !strip.is_pooled()).count();
+    budget.frame_time = diagnostics
+        .as_ref()
+        .and_then(|d| d.get(&FrameTimeDiagnosticsPlugin::FRAME_TIME))
+        .and_then(|d| d.smoothed())
+        .map(|ms| ms as f32 / 1000.0);
+
+    let dt = time.delta_seconds();
+    if budget.is_over() {
+        budget.quality -= budget.degrade_rate * dt;
+    } else if budget.has_headroom() {
+        budget.quality += budget.recover_rate * dt;
+    }
+    budget.quality = budget.quality.clamp(budget.min_quality.min(1.0), 1.0);
+}
+
+impl Plugin for MatrixBudgetPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RainBudget>()
+            .register_type::<RainBudget>()
+            .add_systems(PreUpdate, update_budget);
+    }
+}
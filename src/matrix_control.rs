@@ -0,0 +1,586 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream, UdpSocket},
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Mutex,
+    },
+    thread,
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    matrix_field::{MatrixField, RainMode},
+    matrix_letter::MatrixLetterSettings,
+    matrix_strip::MatrixStripSettings,
+};
+
+/// Text sent by a controller, for the host app to show.
+#[derive(Event, Clone, Debug)]
+pub struct MatrixMessage {
+    pub text: String,
+}
+
+/// Parameters that can be changed by a controller: per field ones, and
+/// the strip and letter settings shared by all fields.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Reflect)]
+pub enum ControlKey {
+    /// Strips per second.
+    Density,
+    Speed,
+    MaxLength,
+    /// `[min, max]` strip lifetime in seconds.
+    Lifetime,
+    /// `[min, max]` letters per second.
+    Spawnrate,
+    /// `"#rrggbb"` or `[r, g, b]`.
+    PaletteHead,
+    PaletteBody,
+    /// `"entities"` or `"shader"`.
+    Mode,
+    /// `[x, y]` strip drift in world units per second.
+    Drift,
+    /// Seconds for a letter to fade from the head to the body color.
+    FadeTime,
+    /// Letter height at depth 0 in world units.
+    CellSize,
+    /// Mean seconds between two glyph changes of a letter.
+    FlipInterval,
+    /// Seconds a dying letter takes to shrink away.
+    ShrinkTime,
+}
+
+impl ControlKey {
+    pub const ALL: [ControlKey; 13] = [
+        ControlKey::Density,
+        ControlKey::Speed,
+        ControlKey::MaxLength,
+        ControlKey::Lifetime,
+        ControlKey::Spawnrate,
+        ControlKey::PaletteHead,
+        ControlKey::PaletteBody,
+        ControlKey::Mode,
+        ControlKey::Drift,
+        ControlKey::FadeTime,
+        ControlKey::CellSize,
+        ControlKey::FlipInterval,
+        ControlKey::ShrinkTime,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ControlKey::Density => "density",
+            ControlKey::Speed => "speed",
+            ControlKey::MaxLength => "max_length",
+            ControlKey::Lifetime => "lifetime",
+            ControlKey::Spawnrate => "spawnrate",
+            ControlKey::PaletteHead => "palette.head",
+            ControlKey::PaletteBody => "palette.body",
+            ControlKey::Mode => "mode",
+            ControlKey::Drift => "strip.drift",
+            ControlKey::FadeTime => "strip.fade_time",
+            ControlKey::CellSize => "strip.cell_size",
+            ControlKey::FlipInterval => "letter.flip_interval",
+            ControlKey::ShrinkTime => "letter.shrink_time",
+        }
+    }
+
+    /// Whether the key is a strip or letter setting rather than a field
+    /// parameter. Settings ignore the command's `field`.
+    pub fn is_setting(self) -> bool {
+        matches!(
+            self,
+            ControlKey::Drift
+                | ControlKey::FadeTime
+                | ControlKey::CellSize
+                | ControlKey::FlipInterval
+                | ControlKey::ShrinkTime
+        )
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|key| key.name() == name)
+    }
+
+    fn get(self, field: &MatrixField) -> Value {
+        match self {
+            ControlKey::Density => json!(1.0 / field.spawn_interval),
+            ControlKey::Speed => json!(field.speed),
+            ControlKey::MaxLength => json!(field.max_length),
+            ControlKey::Lifetime => json!([field.lifetime.start, field.lifetime.end]),
+            ControlKey::Spawnrate => json!([field.spawnrate.start, field.spawnrate.end]),
+            ControlKey::PaletteHead => json!(Srgba::from(field.palette.head).to_hex()),
+            ControlKey::PaletteBody => json!(Srgba::from(field.palette.body).to_hex()),
+            ControlKey::Mode => json!(match field.mode {
+                RainMode::Entities => "entities",
+                RainMode::Shader => "shader",
+            }),
+            _ => Value::Null,
+        }
+    }
+
+    fn get_setting(self, strip: &MatrixStripSettings, letter: &MatrixLetterSettings) -> Value {
+        match self {
+            ControlKey::Drift => json!([strip.drift.x, strip.drift.y]),
+            ControlKey::FadeTime => json!(strip.fade_time),
+            ControlKey::CellSize => json!(strip.cell_size),
+            ControlKey::FlipInterval => json!(letter.flip_interval),
+            ControlKey::ShrinkTime => json!(letter.shrink_time),
+            _ => Value::Null,
+        }
+    }
+
+    fn set(self, field: &mut MatrixField, value: &Value) -> Result<(), String> {
+        match self {
+            ControlKey::Density => field.spawn_interval = 1.0 / positive(value)?,
+            ControlKey::Speed => field.speed = number(value)?,
+            ControlKey::MaxLength => field.max_length = count(value)?,
+            ControlKey::Lifetime => field.lifetime = range(value)?,
+            ControlKey::Spawnrate => field.spawnrate = range(value)?,
+            ControlKey::PaletteHead => field.palette.head = color(value)?,
+            ControlKey::PaletteBody => field.palette.body = color(value)?,
+            ControlKey::Mode => field.mode = mode(value)?,
+            _ => return Err(format!("'{}' is not a field parameter", self.name())),
+        }
+        Ok(())
+    }
+
+    fn set_setting(
+        self,
+        strip: &mut MatrixStripSettings,
+        letter: &mut MatrixLetterSettings,
+        value: &Value,
+    ) -> Result<(), String> {
+        match self {
+            ControlKey::Drift => strip.drift = vec2(value)?,
+            ControlKey::FadeTime => strip.fade_time = positive(value)?,
+            ControlKey::CellSize => strip.cell_size = positive(value)?,
+            ControlKey::FlipInterval => letter.flip_interval = positive(value)?,
+            ControlKey::ShrinkTime => letter.shrink_time = positive(value)?,
+            _ => return Err(format!("'{}' is not a setting", self.name())),
+        }
+        Ok(())
+    }
+}
+
+fn number(value: &Value) -> Result<f32, String> {
+    value
+        .as_f64()
+        .map(|v| v as f32)
+        .ok_or_else(|| format!("expected a number, got {value}"))
+}
+
+fn positive(value: &Value) -> Result<f32, String> {
+    match number(value)? {
+        v if v > 0.0 => Ok(v),
+        v => Err(format!("expected a positive number, got {v}")),
+    }
+}
+
+/// A whole number of at least 1.
+fn count(value: &Value) -> Result<u32, String> {
+    match number(value)? {
+        v if v >= 1.0 && v.fract() == 0.0 && v <= u32::MAX as f32 => Ok(v as u32),
+        v => Err(format!("expected a whole number of at least 1, got {v}")),
+    }
+}
+
+fn vec2(value: &Value) -> Result<Vec2, String> {
+    match value.as_array().map(Vec::as_slice) {
+        Some([x, y]) => Ok(Vec2::new(number(x)?, number(y)?)),
+        _ => Err(format!("expected [x, y], got {value}")),
+    }
+}
+
+fn range(value: &Value) -> Result<std::ops::Range<f32>, String> {
+    match value.as_array().map(Vec::as_slice) {
+        Some([min, max]) => {
+            let (min, max) = (positive(min)?, positive(max)?);
+            if min < max {
+                Ok(min..max)
+            } else {
+                Err(format!("expected min < max, got [{min}, {max}]"))
+            }
+        }
+        _ => Err(format!("expected [min, max], got {value}")),
+    }
+}
+
+fn mode(value: &Value) -> Result<RainMode, String> {
+    match value.as_str() {
+        Some("entities") => Ok(RainMode::Entities),
+        Some("shader") => Ok(RainMode::Shader),
+        _ => Err(format!("expected \"entities\" or \"shader\", got {value}")),
+    }
+}
+
+fn color(value: &Value) -> Result<Color, String> {
+    if let Some(hex) = value.as_str() {
+        return Srgba::hex(hex)
+            .map(Color::from)
+            .map_err(|e| format!("invalid color {hex}: {e}"));
+    }
+    match value.as_array().map(Vec::as_slice) {
+        Some([r, g, b]) => Ok(Color::srgb(number(r)?, number(g)?, number(b)?)),
+        _ => Err(format!("expected \"#rrggbb\" or [r, g, b], got {value}")),
+    }
+}
+
+/// One line of the control protocol.
+///
+/// `field` selects fields by their `Name`; without it `set` applies to all
+/// fields and `get` reads the first one.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum ControlCommand {
+    Set {
+        key: String,
+        value: Value,
+        #[serde(default)]
+        field: Option<String>,
+    },
+    Get {
+        key: String,
+        #[serde(default)]
+        field: Option<String>,
+    },
+    Message {
+        text: String,
+    },
+    List,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct ControlReply {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ControlReply {
+    fn ok(value: Option<Value>) -> Self {
+        Self {
+            ok: true,
+            value,
+            error: None,
+        }
+    }
+
+    fn error(error: impl Into<String>) -> Self {
+        Self {
+            ok: false,
+            value: None,
+            error: Some(error.into()),
+        }
+    }
+}
+
+struct ControlRequest {
+    command: Result<ControlCommand, String>,
+    reply: Option<Sender<ControlReply>>,
+}
+
+/// Parameters a controller may set. Keys outside of it are rejected.
+#[derive(Resource, Reflect, Clone)]
+#[reflect(Resource, Default)]
+pub struct MatrixControlAllowList(pub Vec<ControlKey>);
+
+impl Default for MatrixControlAllowList {
+    fn default() -> Self {
+        Self(ControlKey::ALL.to_vec())
+    }
+}
+
+#[derive(Resource)]
+struct ControlRequests(Mutex<Receiver<ControlRequest>>);
+
+/// Addresses the control server is listening on, e.g. to find the port
+/// picked for an address with port 0.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct MatrixControlAddrs {
+    pub tcp: Option<SocketAddr>,
+    pub osc: Option<SocketAddr>,
+}
+
+/// Listens for JSON-lines commands on a TCP socket and, optionally, OSC
+/// messages on a UDP socket. Every JSON line is answered with one
+/// [`ControlReply`] line.
+///
+/// OSC messages are `/matrix/set/<key>` with the value as arguments and
+/// `/matrix/message` with a string. They are not answered.
+pub struct MatrixControlPlugin {
+    pub addr: String,
+    pub osc_addr: Option<String>,
+}
+
+impl Default for MatrixControlPlugin {
+    fn default() -> Self {
+        Self {
+            addr: "127.0.0.1:7700".to_string(),
+            osc_addr: None,
+        }
+    }
+}
+
+fn serve_client(stream: TcpStream, requests: Sender<ControlRequest>) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            return;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (reply, replies) = channel();
+        let command = serde_json::from_str(&line).map_err(|e| e.to_string());
+        if requests
+            .send(ControlRequest {
+                command,
+                reply: Some(reply),
+            })
+            .is_err()
+        {
+            return;
+        }
+        let Ok(reply) = replies.recv() else {
+            return;
+        };
+        let Ok(mut reply) = serde_json::to_string(&reply) else {
+            return;
+        };
+        reply.push('\n');
+        if writer.write_all(reply.as_bytes()).is_err() {
+            return;
+        }
+    }
+}
+
+fn osc_string(data: &[u8]) -> Option<(&str, &[u8])> {
+    let end = data.iter().position(|&b| b == 0)?;
+    let s = std::str::from_utf8(&data[..end]).ok()?;
+    let padded = (end + 4) & !3;
+    Some((s, data.get(padded..)?))
+}
+
+/// Parses an OSC message into a command. Bundles aren't supported.
+pub fn parse_osc(packet: &[u8]) -> Result<ControlCommand, String> {
+    let (address, rest) = osc_string(packet).ok_or("malformed OSC address")?;
+    let (tags, mut rest) = osc_string(rest).ok_or("malformed OSC type tags")?;
+    let mut args = Vec::new();
+    for tag in tags
+        .strip_prefix(',')
+        .ok_or("malformed OSC type tags")?
+        .chars()
+    {
+        let arg = match tag {
+            'i' | 'f' => {
+                let bytes: [u8; 4] = rest
+                    .get(..4)
+                    .and_then(|b| b.try_into().ok())
+                    .ok_or("truncated OSC argument")?;
+                rest = &rest[4..];
+                if tag == 'i' {
+                    json!(i32::from_be_bytes(bytes))
+                } else {
+                    json!(f32::from_be_bytes(bytes))
+                }
+            }
+            's' => {
+                let (s, tail) = osc_string(rest).ok_or("truncated OSC argument")?;
+                rest = tail;
+                json!(s)
+            }
+            tag => return Err(format!("unsupported OSC type tag '{tag}'")),
+        };
+        args.push(arg);
+    }
+
+    if address == "/matrix/message" {
+        let text = args
+            .first()
+            .and_then(Value::as_str)
+            .ok_or("expected a string")?;
+        return Ok(ControlCommand::Message {
+            text: text.to_string(),
+        });
+    }
+    let key = address
+        .strip_prefix("/matrix/set/")
+        .ok_or_else(|| format!("unknown OSC address {address}"))?;
+    let value = match args.len() {
+        0 => return Err("missing value".to_string()),
+        1 => args.pop().unwrap(),
+        _ => Value::Array(args),
+    };
+    Ok(ControlCommand::Set {
+        key: key.to_string(),
+        value,
+        field: None,
+    })
+}
+
+fn serve_osc(socket: UdpSocket, requests: Sender<ControlRequest>) {
+    let mut buf = [0; 1536];
+    loop {
+        let Ok((len, _)) = socket.recv_from(&mut buf) else {
+            return;
+        };
+        let request = ControlRequest {
+            command: parse_osc(&buf[..len]),
+            reply: None,
+        };
+        if requests.send(request).is_err() {
+            return;
+        }
+    }
+}
+
+fn execute(
+    command: ControlCommand,
+    allowed: &MatrixControlAllowList,
+    fields: &mut Query<(&mut MatrixField, Option<&Name>)>,
+    settings: Option<(&mut MatrixStripSettings, &mut MatrixLetterSettings)>,
+    messages: &mut EventWriter<MatrixMessage>,
+) -> ControlReply {
+    let selected = |name: Option<&Name>, wanted: &Option<String>| match wanted {
+        Some(wanted) => name.is_some_and(|name| name.as_str() == wanted),
+        None => true,
+    };
+    match command {
+        ControlCommand::Set { key, value, field } => {
+            let Some(key) = ControlKey::from_name(&key) else {
+                return ControlReply::error(format!("unknown key '{key}'"));
+            };
+            if !allowed.0.contains(&key) {
+                return ControlReply::error(format!("key '{}' is not settable", key.name()));
+            }
+            if key.is_setting() {
+                let Some((strip, letter)) = settings else {
+                    return ControlReply::error("no strip and letter settings");
+                };
+                return match key.set_setting(strip, letter, &value) {
+                    Ok(()) => ControlReply::ok(None),
+                    Err(error) => ControlReply::error(error),
+                };
+            }
+            let mut found = false;
+            for (mut matrix_field, name) in fields {
+                if !selected(name, &field) {
+                    continue;
+                }
+                found = true;
+                if let Err(error) = key.set(&mut matrix_field, &value) {
+                    return ControlReply::error(error);
+                }
+            }
+            if found {
+                ControlReply::ok(None)
+            } else {
+                ControlReply::error("no such field")
+            }
+        }
+        ControlCommand::Get { key, field } => {
+            let Some(key) = ControlKey::from_name(&key) else {
+                return ControlReply::error(format!("unknown key '{key}'"));
+            };
+            if key.is_setting() {
+                return match settings {
+                    Some((strip, letter)) => ControlReply::ok(Some(key.get_setting(strip, letter))),
+                    None => ControlReply::error("no strip and letter settings"),
+                };
+            }
+            fields
+                .iter()
+                .find(|(_, name)| selected(*name, &field))
+                .map_or_else(
+                    || ControlReply::error("no such field"),
+                    |(matrix_field, _)| ControlReply::ok(Some(key.get(matrix_field))),
+                )
+        }
+        ControlCommand::Message { text } => {
+            messages.send(MatrixMessage { text });
+            ControlReply::ok(None)
+        }
+        ControlCommand::List => ControlReply::ok(Some(json!(allowed
+            .0
+            .iter()
+            .map(|key| key.name())
+            .collect::<Vec<_>>()))),
+    }
+}
+
+fn handle_requests(
+    requests: Res<ControlRequests>,
+    allowed: Res<MatrixControlAllowList>,
+    mut fields: Query<(&mut MatrixField, Option<&Name>)>,
+    (mut strip, mut letter): (
+        Option<ResMut<MatrixStripSettings>>,
+        Option<ResMut<MatrixLetterSettings>>,
+    ),
+    mut messages: EventWriter<MatrixMessage>,
+) {
+    let requests = requests.0.lock().unwrap();
+    for request in requests.try_iter() {
+        let settings = strip.as_deref_mut().zip(letter.as_deref_mut());
+        let reply = match request.command {
+            Ok(command) => execute(command, &allowed, &mut fields, settings, &mut messages),
+            Err(error) => ControlReply::error(error),
+        };
+        match request.reply {
+            Some(sender) => {
+                let _ = sender.send(reply);
+            }
+            None => {
+                if let Some(error) = reply.error {
+                    warn!("matrix control: {error}");
+                }
+            }
+        }
+    }
+}
+
+impl Plugin for MatrixControlPlugin {
+    fn build(&self, app: &mut App) {
+        let (sender, receiver) = channel();
+        let mut addrs = MatrixControlAddrs::default();
+
+        match TcpListener::bind(&self.addr) {
+            Ok(listener) => {
+                addrs.tcp = listener.local_addr().ok();
+                let sender = sender.clone();
+                thread::spawn(move || {
+                    for stream in listener.incoming().flatten() {
+                        let sender = sender.clone();
+                        thread::spawn(move || serve_client(stream, sender));
+                    }
+                });
+            }
+            Err(e) => error!("matrix control: can't listen on {}: {e}", self.addr),
+        }
+
+        if let Some(osc_addr) = &self.osc_addr {
+            match UdpSocket::bind(osc_addr) {
+                Ok(socket) => {
+                    addrs.osc = socket.local_addr().ok();
+                    let sender = sender.clone();
+                    thread::spawn(move || serve_osc(socket, sender));
+                }
+                Err(e) => error!("matrix control: can't listen on {osc_addr}: {e}"),
+            }
+        }
+
+        app.add_event::<MatrixMessage>()
+            .init_resource::<MatrixControlAllowList>()
+            .register_type::<MatrixControlAllowList>()
+            .insert_resource(ControlRequests(Mutex::new(receiver)))
+            .insert_resource(addrs)
+            .add_systems(PreUpdate, handle_requests);
+    }
+}
@@ -1,26 +1,264 @@
-use bevy::prelude::*;
-use rand::{thread_rng, Rng};
+use std::ops::Range;
 
-use crate::{matrix_strip::MatrixStripBundle, utils::exponential_event};
+use bevy::{
+    prelude::*,
+    reflect::{ReflectDeserialize, ReflectSerialize},
+    render::view::RenderLayers,
+};
+use bevy_tweening::Animator;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    matrix_audio::MatrixAudio,
+    matrix_budget::RainBudget,
+    matrix_glyphs::{GlyphMix, MatrixGlyphs},
+    matrix_letter::{
+        LetterDeath, LetterPhase, MatrixLetter, MatrixLetterSettings, MatrixLetterSpawnRequest,
+    },
+    matrix_shader_rain::ShaderRainQuad,
+    matrix_strip::{
+        FlipCurve, LetterFade, MatrixPalette, MatrixStrip, MatrixStripBundle, MatrixStripPool,
+        MatrixStripSettings, SpawnTimer, Spawning, StripSpawned,
+    },
+    utils::{exponential_event, timed, with_rng},
+};
 pub struct MatrixFieldPlugin;
 
-fn spawn_strips(mut commands: Commands, time: Res<Time>) {
-    let mut rng = thread_rng();
-    if exponential_event(0.05, time.delta_seconds()) {
-        commands.spawn(
-            MatrixStripBundle::new(Vec3::new(
-                rng.gen_range(-15.0..18.0),
-                rng.gen_range(0.0..8.0),
-                rng.gen_range(-4.0..1.0),
-            ))
-            .with_lifetime(rng.gen_range(0.5..2.0))
-            .with_spawnrate(rng.gen_range(5.0..15.0)),
-        );
+/// How a [`MatrixField`] draws its rain.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect, Serialize, Deserialize)]
+pub enum RainMode {
+    /// Every strip and letter is an entity.
+    #[default]
+    Entities,
+    /// A single quad over the field draws the rain in a fragment shader,
+    /// see [`crate::matrix_shader_rain`]. Strips already falling when the
+    /// mode changes finish on their own.
+    Shader,
+}
+
+/// A region that rains strips. Strips are spawned as children of the field
+/// entity, so the ranges are in the field's local space and the strips
+/// inherit the field's `RenderLayers`, if it has any.
+#[derive(Component, Reflect, Clone)]
+#[reflect(Component, Default)]
+pub struct MatrixField {
+    pub x: Range<f32>,
+    pub y: Range<f32>,
+    pub z: Range<f32>,
+    /// Mean time in seconds between two new strips.
+    pub spawn_interval: f32,
+    pub lifetime: Range<f32>,
+    pub spawnrate: Range<f32>,
+    pub max_length: u32,
+    /// Drift speed multiplier of the field's strips.
+    pub speed: f32,
+    pub palette: MatrixPalette,
+    pub flip: FlipCurve,
+    pub glyphs: GlyphMix,
+    /// Can be switched while the field is raining.
+    pub mode: RainMode,
+}
+
+impl Default for MatrixField {
+    fn default() -> Self {
+        Self {
+            x: -15.0..18.0,
+            y: 0.0..8.0,
+            z: -4.0..1.0,
+            spawn_interval: 0.05,
+            lifetime: 0.5..2.0,
+            spawnrate: 5.0..15.0,
+            max_length: 40,
+            speed: 1.0,
+            palette: MatrixPalette::default(),
+            flip: FlipCurve::default(),
+            glyphs: GlyphMix::default(),
+            mode: RainMode::default(),
+        }
+    }
+}
+
+#[derive(Bundle, Default)]
+pub struct MatrixFieldBundle {
+    field: MatrixField,
+    transform: SpatialBundle,
+}
+
+impl MatrixFieldBundle {
+    pub fn new(field: MatrixField) -> Self {
+        Self {
+            field,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_transform(mut self, transform: Transform) -> Self {
+        self.transform.transform = transform;
+        self
+    }
+}
+
+/// Captures a running field, its strips and letters, and the strip and letter
+/// settings into a scene. Spawning the scene into a world with the matrix
+/// plugins resumes the rain where it was captured.
+///
+/// Fonts and running tweens aren't serializable: letter text is rebuilt on
+/// load, and letter color fades are saved as a [`LetterFade`] and resumed.
+pub fn field_scene(world: &World, field: Entity) -> DynamicScene {
+    let mut entities = vec![field];
+    let mut index = 0;
+    while let Some(&entity) = entities.get(index) {
+        if let Some(children) = world.get::<Children>(entity) {
+            // Shader rain quads are rebuilt from the field's mode.
+            entities.extend(
+                children
+                    .iter()
+                    .filter(|&&child| !world.entity(child).contains::<ShaderRainQuad>()),
+            );
+        }
+        index += 1;
+    }
+
+    let mut scene = DynamicSceneBuilder::from_world(world)
+        .deny_all()
+        .allow::<Name>()
+        .allow::<Transform>()
+        .allow::<GlobalTransform>()
+        .allow::<Visibility>()
+        .allow::<InheritedVisibility>()
+        .allow::<ViewVisibility>()
+        .allow::<Parent>()
+        .allow::<Children>()
+        .allow::<RenderLayers>()
+        .allow::<MatrixField>()
+        .allow::<MatrixStrip>()
+        .allow::<SpawnTimer>()
+        .allow::<Spawning>()
+        .allow::<MatrixLetter>()
+        .allow::<MatrixLetterSpawnRequest>()
+        .allow::<LetterDeath>()
+        .allow::<LetterPhase>()
+        .allow::<LetterFade>()
+        .deny_all_resources()
+        .allow_resource::<MatrixStripSettings>()
+        .allow_resource::<MatrixLetterSettings>()
+        .extract_entities(entities.into_iter())
+        .extract_resources()
+        .build();
+    // The field's own parent isn't part of the scene.
+    if let Some(root) = scene.entities.iter_mut().find(|e| e.entity == field) {
+        root.components
+            .retain(|component| !component.represents::<Parent>());
+    }
+    for entity in &mut scene.entities {
+        if let Some(animator) = world.get::<Animator<MatrixLetter>>(entity.entity) {
+            let elapsed = animator.tweenable().elapsed();
+            entity.components.push(Box::new(LetterFade(elapsed)));
+        }
+    }
+    scene
+}
+
+pub(crate) fn spawn_strips(
+    mut commands: Commands,
+    query: Query<(
+        Entity,
+        &MatrixField,
+        &GlobalTransform,
+        Option<&RenderLayers>,
+    )>,
+    (audio, budget): (Option<Res<MatrixAudio>>, Option<Res<RainBudget>>),
+    (settings, glyphs): (Res<MatrixStripSettings>, Res<MatrixGlyphs>),
+    mut pool: ResMut<MatrixStripPool>,
+    time: Res<Time>,
+    mut spawned: EventWriter<StripSpawned>,
+) {
+    let modulation = audio.map(|audio| audio.modulation()).unwrap_or_default();
+    if budget.as_ref().is_some_and(|b| b.is_full()) {
+        return;
+    }
+    let quality = budget.as_ref().map_or(1.0, |b| b.quality());
+    for (entity, field, transform, layers) in &query {
+        if field.mode == RainMode::Shader {
+            continue;
+        }
+        let rate = modulation.spawn_rate * quality;
+        let interval = field.spawn_interval / rate.max(f32::EPSILON);
+        if exponential_event(interval, time.delta_seconds()) {
+            let palette = MatrixPalette {
+                head: field.palette.head.rotate_hue(modulation.hue_shift),
+                body: field.palette.body.rotate_hue(modulation.hue_shift),
+                ..field.palette
+            };
+            let (position, lifetime, spawnrate) = with_rng(|rng| {
+                let position = Vec3::new(
+                    rng.gen_range(field.x.clone()),
+                    rng.gen_range(field.y.clone()),
+                    rng.gen_range(field.z.clone()),
+                );
+                let lifetime = rng.gen_range(field.lifetime.clone());
+                (position, lifetime, rng.gen_range(field.spawnrate.clone()))
+            });
+            let strip = pool.spawn(
+                &mut commands,
+                MatrixStripBundle::from_settings(position, &settings)
+                    .with_max_length(
+                        budget
+                            .as_ref()
+                            .map_or(field.max_length, |b| b.max_length(field.max_length)),
+                    )
+                    .with_palette(palette)
+                    .with_flip(field.flip)
+                    .with_glyph_set(match field.glyphs {
+                        GlyphMix::PerLetter => None,
+                        GlyphMix::PerStrip => glyphs.pick_set(),
+                        GlyphMix::Only(set) => Some(set).filter(|&set| set < glyphs.sets.len()),
+                    })
+                    .with_lifetime(lifetime)
+                    .with_spawnrate(spawnrate),
+            );
+            if let Some(layers) = layers {
+                commands.entity(strip).insert(layers.clone());
+            }
+            commands.entity(entity).add_child(strip);
+            spawned.send(StripSpawned {
+                entity: strip,
+                position: transform.transform_point(position),
+            });
+        }
+    }
+}
+
+/// Warns about fields drawing from glyph sets that don't exist, whoever
+/// set them. Their strips mix the sets instead.
+fn check_glyph_mix(
+    fields: Query<(Entity, &MatrixField, Option<&Name>)>,
+    changed: Query<(), Changed<MatrixField>>,
+    glyphs: Res<MatrixGlyphs>,
+) {
+    for (entity, field, name) in &fields {
+        if !glyphs.is_changed() && !changed.contains(entity) {
+            continue;
+        }
+        if let Err(e) = glyphs.check_mix(field.glyphs) {
+            match name {
+                Some(name) => warn!("Field \"{name}\": {e}"),
+                None => warn!("Field {entity}: {e}"),
+            }
+        }
     }
 }
 
 impl Plugin for MatrixFieldPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, spawn_strips);
+        app.register_type::<MatrixField>()
+            .register_type::<MatrixPalette>()
+            .register_type::<RainMode>()
+            .register_type::<Range<f32>>()
+            .register_type_data::<Range<f32>, ReflectSerialize>()
+            .register_type_data::<Range<f32>, ReflectDeserialize>()
+            .add_systems(Update, check_glyph_mix)
+            .add_systems(Update, timed("spawn_strips", spawn_strips));
     }
 }
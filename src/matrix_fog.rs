@@ -0,0 +1,88 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::matrix_letter::{update_color, MatrixLetter};
+
+/// Depth fog: letters between `near` and `far` lose saturation and fade
+/// towards `color` the farther away they are, so the layers of rain read
+/// as near and far.
+///
+/// Depths are world z, with `near` closer to the camera than `far`.
+#[derive(Resource, Reflect, Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[reflect(Resource, Default)]
+pub struct MatrixFog {
+    pub color: Color,
+    pub near: f32,
+    pub far: f32,
+    /// Shape of the fog between `near` and `far`, 1.0 is linear and higher
+    /// values keep the middle layers clearer.
+    pub falloff: f32,
+    /// How much of the color the fog takes at `far`.
+    pub strength: f32,
+    /// How much saturation letters lose at `far`.
+    pub desaturation: f32,
+}
+
+impl Default for MatrixFog {
+    fn default() -> Self {
+        Self {
+            color: Color::srgb(0.0, 0.04, 0.03),
+            near: 0.0,
+            far: -4.0,
+            falloff: 1.0,
+            strength: 0.6,
+            desaturation: 0.7,
+        }
+    }
+}
+
+impl MatrixFog {
+    /// 0.0 at `near` and closer, 1.0 at `far` and beyond.
+    pub fn amount(&self, depth: f32) -> f32 {
+        let range = self.near - self.far;
+        if range.abs() <= f32::EPSILON {
+            return if depth < self.near { 1.0 } else { 0.0 };
+        }
+        ((self.near - depth) / range)
+            .clamp(0.0, 1.0)
+            .powf(self.falloff)
+    }
+
+    /// `color` as seen through the fog at `depth`.
+    pub fn apply(&self, color: Color, depth: f32) -> Color {
+        let amount = self.amount(depth);
+        if amount <= 0.0 {
+            return color;
+        }
+        let color = LinearRgba::from(color);
+        let rgb = Vec3::new(color.red, color.green, color.blue);
+        let luminance = rgb.dot(Vec3::new(0.2126, 0.7152, 0.0722));
+        let gray = rgb.lerp(Vec3::splat(luminance), self.desaturation * amount);
+        let fog = LinearRgba::from(self.color);
+        let rgb = gray.lerp(
+            Vec3::new(fog.red, fog.green, fog.blue),
+            self.strength * amount,
+        );
+        Color::from(LinearRgba::new(rgb.x, rgb.y, rgb.z, color.alpha))
+    }
+}
+
+pub struct MatrixFogPlugin;
+
+/// Letters only update their color when they change, have them all pick
+/// up new fog settings.
+fn refresh_letters(fog: Res<MatrixFog>, mut letters: Query<&mut MatrixLetter>) {
+    if fog.is_changed() && !fog.is_added() {
+        for mut letter in &mut letters {
+            letter.set_changed();
+        }
+    }
+}
+
+impl Plugin for MatrixFogPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MatrixFog>()
+            .register_type::<MatrixFog>()
+            .add_systems(Update, refresh_letters.before(update_color));
+    }
+}
@@ -0,0 +1,168 @@
+use std::ops::Range;
+
+use bevy::{
+    prelude::*,
+    render::extract_resource::{ExtractResource, ExtractResourcePlugin},
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::{exponential_event, with_rng};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Reflect, Serialize, Deserialize)]
+pub enum GlitchKind {
+    /// Horizontal slices of the screen shift sideways.
+    Tear,
+    /// The red and blue channels drift apart.
+    RgbSplit,
+    /// Blocks of the screen stop updating.
+    FrozenBlocks,
+    /// The colors invert.
+    Invert,
+}
+
+impl GlitchKind {
+    pub const ALL: [Self; 4] = [Self::Tear, Self::RgbSplit, Self::FrozenBlocks, Self::Invert];
+}
+
+/// Starts a glitch burst in the post pass of [`crate::post::MatrixPost`]
+/// cameras.
+///
+/// The burst ramps up to `intensity` over the first tenth of `duration`
+/// and fades out over the rest.
+#[derive(Event, Clone, Copy, Debug, PartialEq, Reflect, Serialize, Deserialize)]
+pub struct MatrixGlitch {
+    pub kind: GlitchKind,
+    /// In seconds.
+    pub duration: f32,
+    pub intensity: f32,
+}
+
+impl MatrixGlitch {
+    pub fn new(kind: GlitchKind, duration: f32) -> Self {
+        Self {
+            kind,
+            duration,
+            intensity: 1.0,
+        }
+    }
+
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Level `elapsed` seconds into the burst.
+    pub fn level(&self, elapsed: f32) -> f32 {
+        const ATTACK: f32 = 0.1;
+        let t = elapsed / self.duration.max(f32::EPSILON);
+        let envelope = if t < ATTACK {
+            t / ATTACK
+        } else {
+            (1.0 - (t - ATTACK) / (1.0 - ATTACK)).max(0.0).powi(2)
+        };
+        self.intensity * envelope
+    }
+}
+
+#[derive(Clone, Debug)]
+struct ActiveGlitch {
+    glitch: MatrixGlitch,
+    elapsed: f32,
+}
+
+/// Running glitch bursts, and random ones.
+#[derive(Resource, Reflect, Clone, ExtractResource)]
+#[reflect(Resource, Default)]
+pub struct MatrixGlitches {
+    /// Average seconds between random bursts, `None` for only the ones sent
+    /// as [`MatrixGlitch`] events.
+    pub random_interval: Option<f32>,
+    pub random_duration: Range<f32>,
+    pub random_intensity: Range<f32>,
+    #[reflect(ignore)]
+    active: Vec<ActiveGlitch>,
+    /// Changes with every burst, so every burst looks different.
+    #[reflect(ignore)]
+    seed: f32,
+}
+
+impl Default for MatrixGlitches {
+    fn default() -> Self {
+        Self {
+            random_interval: None,
+            random_duration: 0.1..0.6,
+            random_intensity: 0.3..1.0,
+            active: Vec::new(),
+            seed: 0.0,
+        }
+    }
+}
+
+impl MatrixGlitches {
+    /// Current level of `kind`, the strongest of its running bursts.
+    pub fn level(&self, kind: GlitchKind) -> f32 {
+        self.active
+            .iter()
+            .filter(|active| active.glitch.kind == kind)
+            .map(|active| active.glitch.level(active.elapsed))
+            .fold(0.0, f32::max)
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.active.is_empty()
+    }
+
+    pub fn seed(&self) -> f32 {
+        self.seed
+    }
+
+    fn start(&mut self, glitch: MatrixGlitch) {
+        self.seed = with_rng(|rng| rng.gen_range(0.0..1000.0));
+        self.active.push(ActiveGlitch {
+            glitch,
+            elapsed: 0.0,
+        });
+    }
+}
+
+fn update_glitches(
+    mut glitches: ResMut<MatrixGlitches>,
+    mut events: EventReader<MatrixGlitch>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_seconds();
+    for glitch in events.read() {
+        glitches.start(*glitch);
+    }
+    if let Some(interval) = glitches.random_interval {
+        if exponential_event(interval, dt) {
+            let glitch = with_rng(|rng| MatrixGlitch {
+                kind: GlitchKind::ALL[rng.gen_range(0..GlitchKind::ALL.len())],
+                duration: rng.gen_range(glitches.random_duration.clone()),
+                intensity: rng.gen_range(glitches.random_intensity.clone()),
+            });
+            glitches.start(glitch);
+        }
+    }
+    if glitches.is_active() {
+        for active in &mut glitches.active {
+            active.elapsed += dt;
+        }
+        glitches
+            .active
+            .retain(|active| active.elapsed < active.glitch.duration);
+    }
+}
+
+pub struct MatrixGlitchPlugin;
+
+impl Plugin for MatrixGlitchPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MatrixGlitches>()
+            .register_type::<MatrixGlitches>()
+            .add_event::<MatrixGlitch>()
+            .add_plugins(ExtractResourcePlugin::<MatrixGlitches>::default())
+            .add_systems(PostUpdate, update_glitches);
+    }
+}
@@ -0,0 +1,338 @@
+use ab_glyph::Font as _;
+use bevy::{asset::LoadState, prelude::*};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::with_rng;
+
+/// The glyphs of `fonts/matrix.ttf`.
+pub const MATRIX_CHARS: &str = "0123456789abcdefghijklmnopqrstuvwxABCDEFGHIJKLMNOPQRSTUVWXY";
+
+/// Characters letters can show and the fonts to draw them with.
+#[derive(Clone, Debug, PartialEq, Reflect, Serialize, Deserialize)]
+pub struct GlyphSet {
+    pub name: String,
+    pub chars: String,
+    /// Asset paths, tried in order for every character. The first font
+    /// covering a character draws it.
+    pub fonts: Vec<String>,
+    /// Share of the glyphs drawn from this set when sets are mixed.
+    pub weight: f32,
+}
+
+impl GlyphSet {
+    pub fn new(
+        name: impl Into<String>,
+        chars: impl Into<String>,
+        fonts: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            chars: chars.into(),
+            fonts: fonts.into_iter().map(Into::into).collect(),
+            weight: 1.0,
+        }
+    }
+
+    pub fn with_weight(mut self, weight: f32) -> Self {
+        self.weight = weight;
+        self
+    }
+}
+
+/// How a field's strips pick from the [`MatrixGlyphs`] sets.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Reflect, Serialize, Deserialize)]
+pub enum GlyphMix {
+    /// Every glyph comes from a set picked by weight.
+    #[default]
+    PerLetter,
+    /// Every strip picks a set by weight and keeps it.
+    PerStrip,
+    /// Only the set at this index.
+    Only(usize),
+}
+
+#[derive(Default)]
+struct ResolvedSet {
+    source: Option<GlyphSet>,
+    fonts: Vec<Handle<Font>>,
+    /// Covered characters and the index of the font drawing each.
+    glyphs: Vec<(char, usize)>,
+    missing: Vec<char>,
+    ready: bool,
+}
+
+/// The glyph sets letters are drawn from.
+///
+/// Once a set's fonts are loaded, every character is matched with the first
+/// font covering it. Characters no font covers are logged and left out, so
+/// they never show up as tofu. Until then, letters use the set's first
+/// font for every character.
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct MatrixGlyphs {
+    pub sets: Vec<GlyphSet>,
+    #[reflect(ignore)]
+    resolved: Vec<ResolvedSet>,
+}
+
+impl Default for MatrixGlyphs {
+    fn default() -> Self {
+        Self::new(vec![GlyphSet::new(
+            "matrix",
+            MATRIX_CHARS,
+            ["fonts/matrix.ttf"],
+        )])
+    }
+}
+
+impl MatrixGlyphs {
+    pub fn new(sets: Vec<GlyphSet>) -> Self {
+        Self {
+            sets,
+            resolved: Vec::new(),
+        }
+    }
+
+    /// Whether every set's fonts are loaded and checked.
+    pub fn is_ready(&self) -> bool {
+        self.resolved.len() == self.sets.len() && self.resolved.iter().all(|r| r.ready)
+    }
+
+    /// Characters of `set` that none of its fonts cover.
+    pub fn missing(&self, set: usize) -> &[char] {
+        self.resolved.get(set).map_or(&[], |r| &r.missing)
+    }
+
+    /// A set index, picked by weight.
+    pub fn pick_set(&self) -> Option<usize> {
+        let total: f32 = self.sets.iter().map(|s| s.weight.max(0.0)).sum();
+        if total <= 0.0 {
+            return (!self.sets.is_empty()).then_some(0);
+        }
+        let mut left = with_rng(|rng| rng.gen_range(0.0..total));
+        self.sets.iter().position(|set| {
+            left -= set.weight.max(0.0);
+            left < 0.0
+        })
+    }
+
+    /// Why `mix` can't be drawn from these sets, if it can't.
+    pub fn check_mix(&self, mix: GlyphMix) -> Result<(), String> {
+        match mix {
+            GlyphMix::Only(set) if set >= self.sets.len() => Err(format!(
+                "glyph set {set} doesn't exist, there are {}",
+                self.sets.len()
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// A random glyph of `set`, or of a set picked by weight, and the font
+    /// to draw it with. A `set` that doesn't exist is ignored.
+    pub fn pick(&self, set: Option<usize>) -> Option<(char, Handle<Font>)> {
+        let index = set
+            .filter(|&set| set < self.sets.len())
+            .or_else(|| self.pick_set())?;
+        let set = self.sets.get(index)?;
+        match self.resolved.get(index).filter(|r| r.ready) {
+            Some(resolved) => {
+                let len = resolved.glyphs.len();
+                if len == 0 {
+                    return None;
+                }
+                let (glyph, font) = resolved.glyphs[with_rng(|rng| rng.gen_range(0..len))];
+                Some((glyph, resolved.fonts.get(font).cloned().unwrap_or_default()))
+            }
+            None => {
+                let len = set.chars.chars().count();
+                if len == 0 {
+                    return None;
+                }
+                let glyph = set
+                    .chars
+                    .chars()
+                    .nth(with_rng(|rng| rng.gen_range(0..len)))?;
+                Some((glyph, self.first_font(index)))
+            }
+        }
+    }
+
+    /// Characters of `set` and the fonts drawing them, once the set is
+    /// resolved.
+    pub fn resolved(&self, set: usize) -> Option<impl Iterator<Item = (char, &Handle<Font>)>> {
+        let resolved = self.resolved.get(set).filter(|r| r.ready)?;
+        Some(
+            resolved
+                .glyphs
+                .iter()
+                .filter_map(|&(glyph, font)| Some((glyph, resolved.fonts.get(font)?))),
+        )
+    }
+
+    /// The font drawing `glyph` in the first set that has it.
+    pub fn font_of(&self, glyph: char) -> Handle<Font> {
+        for (index, resolved) in self.resolved.iter().enumerate() {
+            if resolved.ready {
+                if let Some(&(_, font)) = resolved.glyphs.iter().find(|(c, _)| *c == glyph) {
+                    return resolved.fonts.get(font).cloned().unwrap_or_default();
+                }
+            } else if self
+                .sets
+                .get(index)
+                .is_some_and(|set| set.chars.contains(glyph))
+            {
+                return self.first_font(index);
+            }
+        }
+        self.first_font(0)
+    }
+
+    /// The font drawing `glyph` in `set`, or in the first set that has it.
+    pub fn font_in(&self, set: Option<usize>, glyph: char) -> Handle<Font> {
+        let resolved = set.and_then(|set| self.resolved.get(set).filter(|r| r.ready));
+        match resolved.and_then(|r| r.glyphs.iter().find(|(c, _)| *c == glyph)) {
+            Some(&(_, font)) => resolved
+                .and_then(|r| r.fonts.get(font).cloned())
+                .unwrap_or_default(),
+            None => self.font_of(glyph),
+        }
+    }
+
+    /// Starts loading the fonts of changed sets, and checks the sets whose
+    /// fonts are loaded. Returns what is wrong with the sets checked now.
+    pub fn resolve(&mut self, asset_server: &AssetServer, fonts: &Assets<Font>) -> Vec<String> {
+        let mut reports = Vec::new();
+        self.resolved.resize_with(self.sets.len(), Default::default);
+        for (set, resolved) in self.sets.iter().zip(&mut self.resolved) {
+            if resolved.source.as_ref() != Some(set) {
+                *resolved = ResolvedSet {
+                    source: Some(set.clone()),
+                    fonts: set
+                        .fonts
+                        .iter()
+                        .map(|path| asset_server.load(path.clone()))
+                        .collect(),
+                    ..Default::default()
+                };
+            }
+            if resolved.ready {
+                continue;
+            }
+
+            let failed = |font: &Handle<Font>| {
+                matches!(
+                    asset_server.get_load_state(font),
+                    Some(LoadState::Failed(_))
+                )
+            };
+            if !resolved
+                .fonts
+                .iter()
+                .all(|font| fonts.contains(font) || failed(font))
+            {
+                continue;
+            }
+            let loaded: Vec<Option<&Font>> = resolved.fonts.iter().map(|f| fonts.get(f)).collect();
+            if loaded.iter().all(Option::is_none) {
+                reports.push(format!(
+                    "Glyph set \"{}\": none of its fonts loaded",
+                    set.name
+                ));
+                resolved.glyphs = set.chars.chars().map(|c| (c, 0)).collect();
+                resolved.ready = true;
+                continue;
+            }
+            for glyph in set.chars.chars() {
+                let font = loaded
+                    .iter()
+                    .position(|font| font.is_some_and(|f| f.font.glyph_id(glyph).0 != 0));
+                match font {
+                    Some(font) => resolved.glyphs.push((glyph, font)),
+                    None => resolved.missing.push(glyph),
+                }
+            }
+            if !resolved.missing.is_empty() {
+                reports.push(format!(
+                    "Glyph set \"{}\": no font covers {:?}, leaving them out",
+                    set.name,
+                    resolved.missing.iter().collect::<String>()
+                ));
+            }
+            resolved.ready = true;
+        }
+        reports
+    }
+
+    fn first_font(&self, set: usize) -> Handle<Font> {
+        self.resolved
+            .get(set)
+            .and_then(|r| r.fonts.first().cloned())
+            .unwrap_or_default()
+    }
+}
+
+/// Loads the fonts of changed sets and checks which characters they cover.
+pub(crate) fn resolve_glyphs(
+    mut glyphs: ResMut<MatrixGlyphs>,
+    asset_server: Res<AssetServer>,
+    fonts: Res<Assets<Font>>,
+) {
+    // Resolving doesn't count as a change of the sets.
+    for report in glyphs
+        .bypass_change_detection()
+        .resolve(&asset_server, &fonts)
+    {
+        warn!("{report}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glyphs() -> MatrixGlyphs {
+        MatrixGlyphs::new(vec![
+            GlyphSet::new("digits", "0123456789", ["fonts/matrix.ttf"]),
+            GlyphSet::new("letters", "abc", ["fonts/matrix.ttf"]),
+        ])
+    }
+
+    #[test]
+    fn checks_glyph_mix() {
+        let glyphs = glyphs();
+        assert!(glyphs.check_mix(GlyphMix::PerStrip).is_ok());
+        assert!(glyphs.check_mix(GlyphMix::Only(1)).is_ok());
+        assert_eq!(
+            glyphs.check_mix(GlyphMix::Only(2)),
+            Err("glyph set 2 doesn't exist, there are 2".to_string())
+        );
+    }
+
+    #[test]
+    fn picks_from_the_given_set() {
+        let glyphs = glyphs();
+        for _ in 0..100 {
+            let (glyph, _) = glyphs.pick(Some(1)).unwrap();
+            assert!("abc".contains(glyph));
+        }
+    }
+
+    #[test]
+    fn fonts_of_shrunk_sets() {
+        let mut glyphs = glyphs();
+        // Sets removed since the last resolve.
+        glyphs.resolved.resize_with(4, Default::default);
+        glyphs.sets.truncate(1);
+        assert_eq!(glyphs.font_of('a'), glyphs.first_font(0));
+        assert_eq!(glyphs.font_in(Some(3), 'a'), glyphs.first_font(0));
+    }
+
+    #[test]
+    fn missing_set_mixes() {
+        let glyphs = glyphs();
+        for _ in 0..100 {
+            assert!(glyphs.pick(Some(7)).is_some());
+        }
+    }
+}
@@ -0,0 +1,461 @@
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    core_pipeline::{
+        core_2d::graph::{Core2d, Node2d},
+        fullscreen_vertex_shader::fullscreen_shader_vertex_state,
+        tonemapping::Tonemapping,
+    },
+    ecs::query::QueryItem,
+    prelude::*,
+    render::{
+        extract_component::{ExtractComponent, ExtractComponentPlugin},
+        render_asset::{RenderAssetUsages, RenderAssets},
+        render_graph::{
+            NodeRunError, RenderGraphApp, RenderGraphContext, RenderLabel, ViewNode, ViewNodeRunner,
+        },
+        render_resource::{
+            binding_types::{sampler, texture_2d, texture_3d, uniform_buffer},
+            BindGroupEntries, BindGroupLayout, BindGroupLayoutEntries, CachedRenderPipelineId,
+            ColorTargetState, ColorWrites, Extent3d, FilterMode, FragmentState, MultisampleState,
+            Operations, PipelineCache, PrimitiveState, RenderPassColorAttachment,
+            RenderPassDescriptor, RenderPipelineDescriptor, Sampler, SamplerBindingType,
+            SamplerDescriptor, ShaderStages, SpecializedRenderPipeline, SpecializedRenderPipelines,
+            TextureDimension, TextureFormat, TextureSampleType, UniformBuffer,
+        },
+        renderer::{RenderContext, RenderDevice, RenderQueue},
+        texture::{BevyDefault, GpuImage, ImageSampler},
+        view::{ExtractedView, ViewTarget},
+        Render, RenderApp, RenderSet,
+    },
+};
+use serde::{Deserialize, Serialize};
+
+/// Graded looks shipped in `assets/luts`, by name.
+pub const LOOKS: [(&str, &str); 3] = [
+    ("film green", "luts/film_green.cube"),
+    ("amber terminal", "luts/amber_terminal.cube"),
+    ("cyberpunk magenta", "luts/cyberpunk_magenta.cube"),
+];
+
+/// Bevy's tonemapping operators, in a form presets can store.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect, Serialize, Deserialize)]
+pub enum Tonemapper {
+    None,
+    Reinhard,
+    ReinhardLuminance,
+    AcesFitted,
+    AgX,
+    SomewhatBoringDisplayTransform,
+    #[default]
+    TonyMcMapface,
+    BlenderFilmic,
+}
+
+impl Tonemapper {
+    pub const ALL: [Self; 8] = [
+        Self::None,
+        Self::Reinhard,
+        Self::ReinhardLuminance,
+        Self::AcesFitted,
+        Self::AgX,
+        Self::SomewhatBoringDisplayTransform,
+        Self::TonyMcMapface,
+        Self::BlenderFilmic,
+    ];
+}
+
+impl From<Tonemapper> for Tonemapping {
+    fn from(tonemapper: Tonemapper) -> Self {
+        match tonemapper {
+            Tonemapper::None => Tonemapping::None,
+            Tonemapper::Reinhard => Tonemapping::Reinhard,
+            Tonemapper::ReinhardLuminance => Tonemapping::ReinhardLuminance,
+            Tonemapper::AcesFitted => Tonemapping::AcesFitted,
+            Tonemapper::AgX => Tonemapping::AgX,
+            Tonemapper::SomewhatBoringDisplayTransform => {
+                Tonemapping::SomewhatBoringDisplayTransform
+            }
+            Tonemapper::TonyMcMapface => Tonemapping::TonyMcMapface,
+            Tonemapper::BlenderFilmic => Tonemapping::BlenderFilmic,
+        }
+    }
+}
+
+/// Tonemapping and color grading of a camera.
+///
+/// After tonemapping, the image goes through the 3D LUT at `lut`, a `.cube`
+/// file, blended in by `lut_strength`.
+#[derive(Component, Reflect, Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[reflect(Component, Default)]
+pub struct MatrixGrading {
+    pub tonemapping: Tonemapper,
+    /// Asset path of the LUT.
+    pub lut: Option<String>,
+    pub lut_strength: f32,
+}
+
+impl Default for MatrixGrading {
+    fn default() -> Self {
+        Self {
+            tonemapping: Tonemapper::default(),
+            lut: None,
+            lut_strength: 1.0,
+        }
+    }
+}
+
+impl MatrixGrading {
+    pub fn with_tonemapping(mut self, tonemapping: Tonemapper) -> Self {
+        self.tonemapping = tonemapping;
+        self
+    }
+
+    pub fn with_lut(mut self, lut: impl Into<String>) -> Self {
+        self.lut = Some(lut.into());
+        self
+    }
+
+    pub fn with_lut_strength(mut self, lut_strength: f32) -> Self {
+        self.lut_strength = lut_strength;
+        self
+    }
+}
+
+/// The loaded LUT of a [`MatrixGrading`] camera, as a 3D texture.
+#[derive(Component, Clone)]
+pub struct GradingLut {
+    lut: Handle<Image>,
+    strength: f32,
+}
+
+impl ExtractComponent for GradingLut {
+    type QueryData = &'static Self;
+    type QueryFilter = With<Camera>;
+    type Out = Self;
+
+    fn extract_component(lut: QueryItem<Self::QueryData>) -> Option<Self> {
+        (lut.strength > 0.0).then(|| lut.clone())
+    }
+}
+
+/// Rounds to the nearest half float, for the LUT texture. Tiny values flush
+/// to zero.
+fn half_bits(value: f32) -> u16 {
+    let value = value.clamp(0.0, 65504.0);
+    if value < 6.103_515_6e-5 {
+        return 0;
+    }
+    let bits = value.to_bits() + 0x1000;
+    let exponent = (bits >> 23) as i32 - 127 + 15;
+    ((exponent as u32) << 10 | (bits >> 13) & 0x3ff) as u16
+}
+
+/// Parses a 3D LUT in the `.cube` format into a texture indexed by red,
+/// green and blue.
+pub fn parse_cube(text: &str) -> Result<Image, String> {
+    let mut size = None;
+    let mut data = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut words = line.split_whitespace();
+        let keyword = words.next().unwrap_or_default();
+        let values = |words: std::str::SplitWhitespace| {
+            words
+                .map(|w| w.parse::<f32>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("line {}: {e}", number + 1))
+        };
+        match keyword {
+            "TITLE" => {}
+            "LUT_3D_SIZE" => {
+                size = Some(
+                    words
+                        .next()
+                        .and_then(|w| w.parse::<u32>().ok())
+                        .filter(|&s| s >= 2)
+                        .ok_or_else(|| format!("line {}: invalid LUT_3D_SIZE", number + 1))?,
+                );
+            }
+            "LUT_1D_SIZE" => return Err("1D LUTs aren't supported".to_string()),
+            "DOMAIN_MIN" | "DOMAIN_MAX" => {
+                let expected = if keyword == "DOMAIN_MIN" { 0.0 } else { 1.0 };
+                if values(words)?.iter().any(|&v| v != expected) {
+                    return Err(format!(
+                        "line {}: only a 0 to 1 domain is supported",
+                        number + 1
+                    ));
+                }
+            }
+            _ => {
+                let rgb = values(line.split_whitespace())?;
+                if rgb.len() != 3 {
+                    return Err(format!("line {}: expected 3 values", number + 1));
+                }
+                data.extend(rgb.into_iter().chain([1.0]).map(half_bits));
+            }
+        }
+    }
+    let size = size.ok_or("missing LUT_3D_SIZE")?;
+    let expected = (size * size * size * 4) as usize;
+    if data.len() != expected {
+        return Err(format!(
+            "expected {} entries, found {}",
+            expected / 4,
+            data.len() / 4
+        ));
+    }
+    let mut image = Image::new(
+        Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: size,
+        },
+        TextureDimension::D3,
+        data.into_iter().flat_map(u16::to_le_bytes).collect(),
+        TextureFormat::Rgba16Float,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    image.sampler = ImageSampler::linear();
+    Ok(image)
+}
+
+#[derive(Default)]
+pub struct CubeLutLoader;
+
+impl AssetLoader for CubeLutLoader {
+    type Asset = Image;
+    type Settings = ();
+    type Error = String;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Image, String> {
+        let mut text = String::new();
+        reader
+            .read_to_string(&mut text)
+            .await
+            .map_err(|e| e.to_string())?;
+        parse_cube(&text)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["cube"]
+    }
+}
+
+fn apply_grading(
+    mut commands: Commands,
+    cameras: Query<(Entity, &MatrixGrading), Changed<MatrixGrading>>,
+    asset_server: Res<AssetServer>,
+) {
+    for (entity, grading) in &cameras {
+        let mut camera = commands.entity(entity);
+        camera.insert(Tonemapping::from(grading.tonemapping));
+        match &grading.lut {
+            Some(path) => camera.insert(GradingLut {
+                lut: asset_server.load(path.clone()),
+                strength: grading.lut_strength,
+            }),
+            None => camera.remove::<GradingLut>(),
+        };
+    }
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+pub struct GradingLabel;
+
+#[derive(Resource)]
+struct GradingPipeline {
+    layout: BindGroupLayout,
+    sampler: Sampler,
+    shader: Handle<Shader>,
+}
+
+impl FromWorld for GradingPipeline {
+    fn from_world(render_world: &mut World) -> Self {
+        let render_device = render_world.resource::<RenderDevice>();
+        let layout = render_device.create_bind_group_layout(
+            "matrix_grading_bind_group_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::FRAGMENT,
+                (
+                    texture_2d(TextureSampleType::Float { filterable: true }),
+                    sampler(SamplerBindingType::Filtering),
+                    texture_3d(TextureSampleType::Float { filterable: true }),
+                    sampler(SamplerBindingType::Filtering),
+                    uniform_buffer::<f32>(false),
+                ),
+            ),
+        );
+        let sampler = render_device.create_sampler(&SamplerDescriptor {
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..default()
+        });
+        let shader = render_world
+            .resource::<AssetServer>()
+            .load("shaders/grading.wgsl");
+        Self {
+            layout,
+            sampler,
+            shader,
+        }
+    }
+}
+
+impl SpecializedRenderPipeline for GradingPipeline {
+    type Key = TextureFormat;
+
+    fn specialize(&self, format: Self::Key) -> RenderPipelineDescriptor {
+        RenderPipelineDescriptor {
+            label: Some("matrix_grading".into()),
+            layout: vec![self.layout.clone()],
+            vertex: fullscreen_shader_vertex_state(),
+            fragment: Some(FragmentState {
+                shader: self.shader.clone(),
+                shader_defs: vec![],
+                entry_point: "fragment".into(),
+                targets: vec![Some(ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            push_constant_ranges: Vec::new(),
+        }
+    }
+}
+
+#[derive(Component)]
+struct PreparedGrading {
+    pipeline: CachedRenderPipelineId,
+    strength: UniformBuffer<f32>,
+}
+
+fn prepare_grading(
+    mut commands: Commands,
+    pipeline_cache: Res<PipelineCache>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<GradingPipeline>>,
+    grading_pipeline: Res<GradingPipeline>,
+    (render_device, render_queue): (Res<RenderDevice>, Res<RenderQueue>),
+    views: Query<(Entity, &ExtractedView, &GradingLut)>,
+) {
+    for (entity, view, lut) in &views {
+        let format = if view.hdr {
+            ViewTarget::TEXTURE_FORMAT_HDR
+        } else {
+            TextureFormat::bevy_default()
+        };
+        let mut strength = UniformBuffer::from(lut.strength);
+        strength.write_buffer(&render_device, &render_queue);
+        commands.entity(entity).insert(PreparedGrading {
+            pipeline: pipelines.specialize(&pipeline_cache, &grading_pipeline, format),
+            strength,
+        });
+    }
+}
+
+#[derive(Default)]
+struct GradingNode;
+
+impl ViewNode for GradingNode {
+    type ViewQuery = (
+        &'static ViewTarget,
+        &'static GradingLut,
+        &'static PreparedGrading,
+    );
+
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        (target, lut, prepared): QueryItem<Self::ViewQuery>,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let grading_pipeline = world.resource::<GradingPipeline>();
+        let Some(pipeline) = world
+            .resource::<PipelineCache>()
+            .get_render_pipeline(prepared.pipeline)
+        else {
+            return Ok(());
+        };
+        // Until the LUT is loaded, the image is left as is.
+        let Some(lut) = world.resource::<RenderAssets<GpuImage>>().get(&lut.lut) else {
+            return Ok(());
+        };
+        let Some(strength) = prepared.strength.binding() else {
+            return Ok(());
+        };
+
+        let post_process = target.post_process_write();
+        let bind_group = render_context.render_device().create_bind_group(
+            "matrix_grading_bind_group",
+            &grading_pipeline.layout,
+            &BindGroupEntries::sequential((
+                post_process.source,
+                &grading_pipeline.sampler,
+                &lut.texture_view,
+                &lut.sampler,
+                strength,
+            )),
+        );
+        let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+            label: Some("matrix_grading_pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: post_process.destination,
+                resolve_target: None,
+                ops: Operations::default(),
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_render_pipeline(pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+        Ok(())
+    }
+}
+
+pub struct MatrixGradingPlugin;
+
+impl Plugin for MatrixGradingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset_loader::<CubeLutLoader>()
+            .register_type::<MatrixGrading>()
+            .add_plugins(ExtractComponentPlugin::<GradingLut>::default())
+            .add_systems(Update, apply_grading);
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .init_resource::<SpecializedRenderPipelines<GradingPipeline>>()
+            .add_systems(Render, prepare_grading.in_set(RenderSet::Prepare))
+            .add_render_graph_node::<ViewNodeRunner<GradingNode>>(Core2d, GradingLabel)
+            .add_render_graph_edges(
+                Core2d,
+                (
+                    Node2d::Tonemapping,
+                    GradingLabel,
+                    Node2d::EndMainPassPostProcessing,
+                ),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app.init_resource::<GradingPipeline>();
+    }
+}
@@ -1,38 +1,221 @@
-use std::time::Duration;
+use std::{
+    f32::consts::{FRAC_PI_2, PI},
+    time::Duration,
+};
 
-use super::utils::*;
-use bevy::prelude::*;
-use bevy_tweening::{lens::TransformScaleLens, *};
-use rand::{thread_rng, Rng};
+use super::{
+    matrix_audio::MatrixAudio,
+    matrix_budget::RainBudget,
+    matrix_fog::MatrixFog,
+    matrix_glyphs::{resolve_glyphs, MatrixGlyphs},
+    matrix_pointer::MatrixPointer,
+    matrix_strip::{MatrixStrip, Spawning},
+    utils::*,
+};
+use bevy::{
+    prelude::*,
+    sprite::Anchor,
+    text::{Text2dBounds, TextLayoutInfo},
+    transform::helper::TransformHelper,
+};
+use bevy_tweening::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 
-#[derive(Component, Default)]
+#[derive(Component, Reflect)]
+#[reflect(Component, Default)]
 pub struct MatrixLetter {
+    glyph: char,
     mul_color: Color,
     color: Color,
+    /// Linear intensity of `color`, above 1.0 to feed bloom.
+    emission: f32,
+    highlight: f32,
+    /// Size the glyph is rasterized at, in pixels.
+    font_size: f32,
+}
+
+impl Default for MatrixLetter {
+    fn default() -> Self {
+        Self {
+            glyph: ' ',
+            mul_color: Color::WHITE,
+            color: Color::WHITE,
+            emission: default_emission(),
+            highlight: 0.0,
+            font_size: default_font_size(),
+        }
+    }
+}
+
+impl MatrixLetter {
+    /// The text color, before fog.
+    fn text_color(&self, boost: f32) -> Color {
+        let color = LinearRgba::from(Color::from(Srgba::from_vec4(
+            Srgba::from(self.color).to_vec4() * Srgba::from(self.mul_color).to_vec4(),
+        )));
+        let intensity = self.emission * boost;
+        Color::from(LinearRgba::from_vec4(
+            color.to_vec4() * Vec4::new(intensity, intensity, intensity, 1.0),
+        ))
+    }
 }
 
-#[derive(Component)]
+#[derive(Component, Reflect)]
+#[reflect(Component)]
 #[component(storage = "SparseSet")]
-struct MatrixLetterSpawnRequest {
+pub(crate) struct MatrixLetterSpawnRequest {
     pos: Vec3,
+    glyph: char,
+    rotation: Quat,
     mul_color: Color,
     color: Color,
+    emission: f32,
     lifetime: f32,
 }
 
+/// Lifecycle of a letter. Letters go through the phases in order and are
+/// despawned once `Dead`.
+#[derive(Component, Reflect, Clone, Debug, PartialEq)]
+#[reflect(Component)]
+pub enum LetterPhase {
+    /// Waiting for its glyph and text.
+    Spawning,
+    /// Showing, until its [`LetterDeath`] timer runs out.
+    Alive,
+    /// Shrinking away from `scale` over the settings' `shrink_time`.
+    Dying {
+        timer: Timer,
+        scale: Vec3,
+    },
+    Dead,
+    /// Hidden in the [`MatrixLetterPool`], waiting to be reused.
+    Pooled,
+}
+
+impl LetterPhase {
+    fn dying(scale: Vec3, shrink_time: f32) -> Self {
+        Self::Dying {
+            timer: Timer::from_seconds(shrink_time, TimerMode::Once),
+            scale,
+        }
+    }
+}
+
+/// Dead letters kept hidden for reuse, so the rain doesn't spawn and
+/// despawn entities all the time.
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct MatrixLetterPool {
+    /// Most letters kept in the pool, dead letters beyond it are despawned.
+    pub capacity: usize,
+    /// Letters created at startup.
+    pub warm_up: usize,
+    free: Vec<Entity>,
+}
+
+impl Default for MatrixLetterPool {
+    fn default() -> Self {
+        Self {
+            capacity: 4096,
+            warm_up: 1024,
+            free: Vec::new(),
+        }
+    }
+}
+
+impl MatrixLetterPool {
+    /// Spawns a letter, reusing a pooled one if there is any.
+    pub fn spawn(&mut self, commands: &mut Commands, letter: MatrixLetterBundle) -> Entity {
+        // Pooled letters may have been despawned by the app.
+        while let Some(entity) = self.free.pop() {
+            if let Some(mut pooled) = commands.get_entity(entity) {
+                pooled.insert(letter);
+                return entity;
+            }
+        }
+        commands.spawn(letter).id()
+    }
+
+    /// Number of letters waiting to be reused.
+    pub fn len(&self) -> usize {
+        self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.free.is_empty()
+    }
+}
+
 pub struct MatrixLetterPlugin;
 #[derive(Bundle)]
 pub struct MatrixLetterBundle {
     request: MatrixLetterSpawnRequest,
+    phase: LetterPhase,
 }
 
-#[derive(Resource)]
-struct MatrixLetterData {
-    font: Handle<Font>,
-    font_size: f32,
+/// Settings shared by all letters.
+#[derive(Resource, Reflect, Clone)]
+#[reflect(Resource)]
+pub struct MatrixLetterSettings {
+    /// Mean time between two glyph changes of a letter, in seconds.
+    pub flip_interval: f32,
+    /// Time a dying letter takes to shrink away.
+    pub shrink_time: f32,
+    /// Resolution text is rasterized at, in pixels per world unit. Match
+    /// the camera's to keep close letters crisp.
+    pub pixels_per_unit: f32,
+    /// Chance of a new glyph being mirrored horizontally.
+    pub mirror_chance: f32,
+    /// Chance of a new glyph being turned 90° either way.
+    pub rotate_chance: f32,
+    /// Chance of a new glyph being upside down.
+    pub upside_down_chance: f32,
+}
+
+impl Default for MatrixLetterSettings {
+    fn default() -> Self {
+        Self {
+            flip_interval: 2.0,
+            shrink_time: 0.5,
+            pixels_per_unit: 64.0,
+            mirror_chance: 0.0,
+            rotate_chance: 0.0,
+            upside_down_chance: 0.0,
+        }
+    }
+}
+
+impl MatrixLetterSettings {
+    /// Font size for a glyph `height` world units tall. Sizes are rounded
+    /// to steps of 4 pixels, every size gets its own glyph atlas.
+    pub fn font_size(&self, height: f32) -> f32 {
+        (self.pixels_per_unit * height / 4.0)
+            .round()
+            .clamp(2.0, 64.0)
+            * 4.0
+    }
+
+    /// Random rotation of a new glyph. Mirroring turns the glyph around
+    /// the vertical axis, so the letter's scale is left to the shrink.
+    pub(crate) fn glyph_rotation(&self) -> Quat {
+        with_rng(|rng| {
+            let mut turns = 0.0;
+            if rng.gen_bool(self.rotate_chance.clamp(0.0, 1.0) as f64) {
+                turns += if rng.gen() { 1.0 } else { -1.0 };
+            }
+            if rng.gen_bool(self.upside_down_chance.clamp(0.0, 1.0) as f64) {
+                turns += 2.0;
+            }
+            let mirror = rng.gen_bool(self.mirror_chance.clamp(0.0, 1.0) as f64);
+            Quat::from_rotation_z(turns * FRAC_PI_2)
+                * Quat::from_rotation_y(if mirror { PI } else { 0.0 })
+        })
+    }
 }
 
-#[derive(Component)]
+#[derive(Component, Reflect)]
+#[reflect(Component)]
 pub struct LetterDeath(Timer);
 
 impl Default for LetterDeath {
@@ -41,9 +224,182 @@ impl Default for LetterDeath {
     }
 }
 
+fn default_font_size() -> f32 {
+    64.0
+}
+
+fn default_emission() -> f32 {
+    1.0
+}
+
+/// World height of one local unit of a letter's strip.
+fn glyph_height(strip: &GlobalTransform) -> f32 {
+    strip.affine().matrix3.y_axis.length()
+}
+
+/// Global transform of `entity` composed from its and its ancestors'
+/// `Transform`s, so it is right before transform propagation ran.
+fn global_transform(world: &World, entity: Entity) -> Option<GlobalTransform> {
+    let mut global = GlobalTransform::from(*world.get::<Transform>(entity)?);
+    let mut current = entity;
+    while let Some(parent) = world.get::<Parent>(current) {
+        current = parent.get();
+        let Some(transform) = world.get::<Transform>(current) else {
+            break;
+        };
+        global = GlobalTransform::from(*transform) * global;
+    }
+    Some(global)
+}
+
+/// Snapshot of a letter, part of a [`crate::matrix_state::MatrixState`].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LetterState {
+    pub translation: Vec3,
+    pub scale: Vec3,
+    #[serde(default)]
+    pub rotation: Quat,
+    #[serde(default = "default_font_size")]
+    pub font_size: f32,
+    pub glyph: char,
+    pub color: Color,
+    pub mul_color: Color,
+    #[serde(default = "default_emission")]
+    pub emission: f32,
+    pub lifetime: Duration,
+    pub age: Duration,
+    /// Time into the head to body color fade, if it started.
+    pub fade: Option<Duration>,
+    /// Time into the death shrink, if it started.
+    pub shrink: Option<Duration>,
+}
+
+impl LetterState {
+    pub(crate) fn capture(world: &World, entity: Entity) -> Option<Self> {
+        // Letters spawned this frame are still requests, take them as they
+        // will be once handled.
+        if let Some(request) = world.get::<MatrixLetterSpawnRequest>(entity) {
+            let height = world
+                .get::<Parent>(entity)
+                .and_then(|parent| global_transform(world, parent.get()))
+                .as_ref()
+                .map_or(1.0, glyph_height);
+            let font_size = world.resource::<MatrixLetterSettings>().font_size(height);
+            return Some(Self {
+                translation: request.pos,
+                scale: Vec3::splat(1.0 / font_size),
+                rotation: request.rotation,
+                font_size,
+                glyph: request.glyph,
+                color: request.color,
+                mul_color: request.mul_color,
+                emission: request.emission,
+                lifetime: Duration::from_secs_f32(request.lifetime),
+                age: Duration::ZERO,
+                fade: None,
+                shrink: None,
+            });
+        }
+        let letter = world.get::<MatrixLetter>(entity)?;
+        let transform = world.get::<Transform>(entity)?;
+        let death = world.get::<LetterDeath>(entity)?;
+        let shrink = match world.get::<LetterPhase>(entity)? {
+            LetterPhase::Spawning | LetterPhase::Alive => None,
+            LetterPhase::Dying { timer, .. } => Some(timer.elapsed()),
+            LetterPhase::Dead | LetterPhase::Pooled => return None,
+        };
+        Some(Self {
+            translation: transform.translation,
+            scale: transform.scale,
+            rotation: transform.rotation,
+            font_size: letter.font_size,
+            glyph: letter.glyph,
+            color: letter.color,
+            mul_color: letter.mul_color,
+            emission: letter.emission,
+            lifetime: death.0.duration(),
+            age: death.0.elapsed(),
+            fade: world
+                .get::<Animator<MatrixLetter>>(entity)
+                .map(|animator| animator.tweenable().elapsed()),
+            shrink,
+        })
+    }
+
+    /// Spawns the letter. The color fade depends on the strip and is left to
+    /// the caller.
+    pub(crate) fn spawn(&self, world: &mut World) -> Entity {
+        let font_size = self.font_size;
+        let text_style = TextStyle {
+            font: world.resource::<MatrixGlyphs>().font_of(self.glyph),
+            font_size,
+            color: self.color,
+        };
+        let shrink_time = world.resource::<MatrixLetterSettings>().shrink_time;
+        let mut death = Timer::new(self.lifetime, TimerMode::Once);
+        death.set_elapsed(self.age);
+        // A letter captured right as it died hasn't started shrinking yet.
+        let shrink = self
+            .shrink
+            .or((self.age >= self.lifetime).then_some(Duration::ZERO));
+        let phase = match shrink {
+            Some(elapsed) => {
+                let mut phase = LetterPhase::dying(Vec3::splat(1.0 / font_size), shrink_time);
+                if let LetterPhase::Dying { timer, .. } = &mut phase {
+                    timer.set_elapsed(elapsed);
+                }
+                phase
+            }
+            None => LetterPhase::Alive,
+        };
+        world
+            .spawn((
+                MatrixLetter {
+                    glyph: self.glyph,
+                    color: self.color,
+                    mul_color: self.mul_color,
+                    emission: self.emission,
+                    highlight: 0.0,
+                    font_size,
+                },
+                Text2dBundle {
+                    transform: Transform::from_translation(self.translation)
+                        .with_rotation(self.rotation)
+                        .with_scale(self.scale),
+                    text: Text::from_section(self.glyph, text_style)
+                        .with_justify(JustifyText::Center),
+                    ..Default::default()
+                },
+                LetterDeath(death),
+                phase,
+            ))
+            .id()
+    }
+}
+
+/// Sent when a letter appears at the head of its strip.
+#[derive(Event, Clone, Debug)]
+pub struct LetterSpawned {
+    pub entity: Entity,
+    /// World position.
+    pub position: Vec3,
+    pub glyph: char,
+}
+
+/// Sent when a letter's lifetime ends and it starts shrinking away.
+#[derive(Event, Clone, Debug)]
+pub struct LetterDied {
+    pub entity: Entity,
+    /// World position.
+    pub position: Vec3,
+    pub glyph: char,
+}
+
 pub struct MatrixLetterLens {
     pub start: Color,
     pub end: Color,
+    pub start_emission: f32,
+    pub end_emission: f32,
 }
 
 impl Lens<MatrixLetter> for MatrixLetterLens {
@@ -51,6 +407,7 @@ impl Lens<MatrixLetter> for MatrixLetterLens {
         let (s_start, s_end) = (Srgba::from(self.start), Srgba::from(self.end));
         let col = s_end * ratio + s_start * (1.0 - ratio);
         target.color = Color::from(col);
+        target.emission = self.start_emission.lerp(self.end_emission, ratio);
     }
 }
 
@@ -59,13 +416,24 @@ impl MatrixLetterBundle {
         Self {
             request: MatrixLetterSpawnRequest {
                 pos,
+                glyph: ' ',
+                rotation: Quat::IDENTITY,
                 mul_color: Color::srgba(1.0, 1.0, 1.0, 1.0),
                 color: Color::WHITE,
+                emission: 1.0,
                 lifetime: 10.0,
             },
+            phase: LetterPhase::Spawning,
         }
     }
 
+    /// The glyph the letter starts with, and its rotation.
+    pub fn with_glyph(mut self, glyph: char, rotation: Quat) -> Self {
+        self.request.glyph = glyph;
+        self.request.rotation = rotation;
+        self
+    }
+
     pub fn with_brightness(mut self, brightness: f32) -> Self {
         let smc = Srgba::from(self.request.mul_color);
         self.request.mul_color = Color::from(
@@ -81,55 +449,106 @@ impl MatrixLetterBundle {
         self
     }
 
+    pub fn with_emission(mut self, emission: f32) -> Self {
+        self.request.emission = emission;
+        self
+    }
+
     pub fn with_lifetime(mut self, lifetime: f32) -> Self {
         self.request.lifetime = lifetime;
         self
     }
 }
 
-fn make_matrix_character() -> String {
-    let mut rng = thread_rng();
-    let r: u8 = rng.gen_range(0..=58);
-    let c = match r {
-        0..=9 => '0' as u8 + r,
-        10..=33 => 'a' as u8 + r - 10,
-        _ => 'A' as u8 + r - 34,
-    } as char;
-    return c.to_string();
-}
-
-fn change_text(mut query: Query<(&mut Text), With<MatrixLetter>>, time: Res<Time>) {
+fn change_text(
+    mut query: Query<(
+        Entity,
+        &mut Text,
+        &mut Transform,
+        &mut MatrixLetter,
+        &LetterPhase,
+        &LetterDeath,
+        Option<&Parent>,
+    )>,
+    strips: Query<(&MatrixStrip, Has<Spawning>)>,
+    glyphs: Res<MatrixGlyphs>,
+    pointer: Option<Res<MatrixPointer>>,
+    (audio, budget): (Option<Res<MatrixAudio>>, Option<Res<RainBudget>>),
+    settings: Res<MatrixLetterSettings>,
+    time: Res<Time>,
+) {
     let dt = time.delta_seconds();
-    for mut t in query.iter_mut() {
-        if exponential_event(2.0, dt) {
-            t.sections[0].value = make_matrix_character();
+    let scramble = pointer.map_or(0.0, |p| p.scramble - 1.0);
+    let flip_rate =
+        audio.map_or(1.0, |a| a.modulation().flip_rate) * budget.map_or(1.0, |b| b.quality());
+    for (entity, mut t, mut transform, mut letter, phase, death, parent) in query.iter_mut() {
+        if *phase == LetterPhase::Pooled {
+            continue;
+        }
+        let strip = parent.and_then(|parent| strips.get(parent.get()).ok());
+        let curve = strip.map_or(1.0, |(strip, spawning)| {
+            strip.flip_rate(entity, spawning, death.0.elapsed_secs())
+        });
+        let rate = flip_rate * (curve + scramble * letter.highlight);
+        if rate > 0.0 && exponential_event(settings.flip_interval / rate, dt) {
+            let Some((glyph, font)) = glyphs.pick(strip.and_then(|(s, _)| s.glyph_set())) else {
+                continue;
+            };
+            letter.bypass_change_detection().glyph = glyph;
+            t.sections[0].value = glyph.to_string();
+            t.sections[0].style.font = font;
+            transform.rotation = settings.glyph_rotation();
         }
     }
 }
 
 fn spawn_request_handler(
     mut commands: Commands,
-    query: Query<(Entity, &MatrixLetterSpawnRequest)>,
-    data: Res<MatrixLetterData>,
+    query: Query<(Entity, &MatrixLetterSpawnRequest, Option<&Parent>)>,
+    strips: Query<&MatrixStrip>,
+    // A strip spawned this frame has no global transform yet.
+    transforms: TransformHelper,
+    glyphs: Res<MatrixGlyphs>,
+    settings: Res<MatrixLetterSettings>,
+    mut spawned: EventWriter<LetterSpawned>,
 ) {
-    for (entity, request) in &query {
+    for (entity, request, parent) in &query {
+        let parent_global =
+            parent.and_then(|parent| transforms.compute_global_transform(parent.get()).ok());
+        let position =
+            parent_global.map_or(request.pos, |parent| parent.transform_point(request.pos));
+        let strip = parent.and_then(|parent| strips.get(parent.get()).ok());
+        let glyph = request.glyph;
+        let font = glyphs.font_in(strip.and_then(MatrixStrip::glyph_set), glyph);
+        // Rasterize for the size the letter is drawn at.
+        let font_size = settings.font_size(parent_global.as_ref().map_or(1.0, glyph_height));
         let text_style = TextStyle {
-            font: data.font.clone(),
-            font_size: data.font_size,
+            font,
+            font_size,
             color: Color::from(Srgba::from_vec4(
                 Srgba::new(1.0, 1.0, 1.0, 0.0).to_vec4() * Srgba::from(request.mul_color).to_vec4(),
             )),
         };
-        let fade_in = commands
+        spawned.send(LetterSpawned {
+            entity,
+            position,
+            glyph,
+        });
+        commands
             .entity(entity)
             .insert(MatrixLetter {
+                glyph,
                 color: request.color,
                 mul_color: request.mul_color,
+                emission: request.emission,
+                highlight: 0.0,
+                font_size,
             })
             .insert(Text2dBundle {
-                transform: Transform::from_scale(Vec3::splat(1.0 / data.font_size))
-                    .with_translation(request.pos),
-                text: Text::from_section(make_matrix_character(), text_style.clone())
+                transform: Transform::from_scale(Vec3::splat(1.0 / font_size))
+                    .with_translation(request.pos)
+                    .with_rotation(request.rotation),
+                text: Text::from_section(glyph, text_style.clone())
                     .with_justify(JustifyText::Center),
                 ..Default::default()
             })
@@ -137,75 +556,213 @@ fn spawn_request_handler(
                 Duration::from_secs_f32(request.lifetime),
                 TimerMode::Once,
             )))
+            .insert(LetterPhase::Alive)
             .remove::<MatrixLetterSpawnRequest>();
     }
 }
 
-fn letter_death(
+/// Rebuilds the text of letters loaded from a scene. Fonts can't be
+/// serialized, so scenes only carry the [`MatrixLetter`] and its transform.
+fn restore_letter_text(
     mut commands: Commands,
+    query: Query<(Entity, &MatrixLetter), Without<Text>>,
+    glyphs: Res<MatrixGlyphs>,
+) {
+    for (entity, letter) in &query {
+        let text_style = TextStyle {
+            font: glyphs.font_of(letter.glyph),
+            font_size: letter.font_size,
+            color: letter.text_color(1.0),
+        };
+        commands.entity(entity).insert((
+            Text::from_section(letter.glyph, text_style).with_justify(JustifyText::Center),
+            Anchor::default(),
+            Text2dBounds::default(),
+            TextLayoutInfo::default(),
+        ));
+    }
+}
+
+fn letter_death(
     mut query: Query<(
         Entity,
-        Option<&Parent>,
-        &mut MatrixLetter,
         &mut LetterDeath,
+        &mut LetterPhase,
+        &MatrixLetter,
         &Transform,
+        &GlobalTransform,
     )>,
+    settings: Res<MatrixLetterSettings>,
     time: Res<Time>,
+    mut died: EventWriter<LetterDied>,
 ) {
-    for (entity, parent, mut letter, mut letter_death, transform) in &mut query {
+    for (entity, mut letter_death, mut phase, letter, transform, global) in &mut query {
+        if *phase != LetterPhase::Alive {
+            continue;
+        }
         letter_death.0.tick(time.delta());
-        if letter_death.0.just_finished() {
-            let tween = Tween::new(
-                EaseFunction::QuadraticOut,
-                //TweeningType::Once,
-                Duration::from_secs_f32(0.5),
-                TransformScaleLens {
-                    start: transform.scale.clone(),
-                    end: Vec3::new(0.0, 0.0, 1.0),
-                },
-            )
-            .with_repeat_count(RepeatCount::Finite(1));
-            commands.entity(entity).insert(Animator::new(tween));
+        if letter_death.0.finished() {
+            died.send(LetterDied {
+                entity,
+                position: global.translation(),
+                glyph: letter.glyph,
+            });
+            *phase = LetterPhase::dying(transform.scale, settings.shrink_time);
+        }
+    }
+}
+
+fn letter_dying(mut query: Query<(&mut LetterPhase, &mut Transform)>, time: Res<Time>) {
+    for (mut phase, mut transform) in &mut query {
+        let LetterPhase::Dying { timer, scale } = &mut *phase else {
+            continue;
+        };
+        timer.tick(time.delta());
+        // Quadratic ease out.
+        let t = 1.0 - (1.0 - timer.fraction()).powi(2);
+        transform.scale = scale.lerp(Vec3::new(0.0, 0.0, 1.0), t);
+        if timer.finished() {
+            *phase = LetterPhase::Dead;
         }
     }
 }
 
 fn letter_despawn(
     mut commands: Commands,
-    query: Query<(Entity, Option<&Parent>, &Animator<Transform>)>,
+    mut query: Query<
+        (Entity, Option<&Parent>, &mut LetterPhase, &mut Visibility),
+        With<MatrixLetter>,
+    >,
+    mut pool: ResMut<MatrixLetterPool>,
 ) {
-    for (entity, parent, animator) in &query {
-        if animator.tweenable().progress() > 0.999 {
-            if let Some(parent) = parent {
-                commands.entity(parent.get()).remove_children(&[entity]);
-            }
+    for (entity, parent, mut phase, mut visibility) in &mut query {
+        if *phase != LetterPhase::Dead {
+            continue;
+        }
+        if let Some(parent) = parent {
+            commands.entity(parent.get()).remove_children(&[entity]);
+        }
+        if pool.free.len() < pool.capacity {
+            *phase = LetterPhase::Pooled;
+            *visibility = Visibility::Hidden;
+            // The fade belongs to the old strip, the next one starts its own.
+            commands.entity(entity).remove::<Animator<MatrixLetter>>();
+            pool.free.push(entity);
+        } else {
             commands.entity(entity).despawn_recursive();
         }
     }
 }
 
-fn update_color(mut query: Query<(&mut Text, &MatrixLetter), Changed<MatrixLetter>>) {
-    for (mut text, letter) in &mut query {
-        text.sections[0].style.color = Color::from(Srgba::from_vec4(
-            Srgba::from(letter.color).to_vec4() * Srgba::from(letter.mul_color).to_vec4(),
-        ))
+fn warm_up_pool(
+    mut commands: Commands,
+    mut pool: ResMut<MatrixLetterPool>,
+    settings: Res<MatrixLetterSettings>,
+) {
+    let count = pool
+        .warm_up
+        .min(pool.capacity)
+        .saturating_sub(pool.free.len());
+    for _ in 0..count {
+        let text_style = TextStyle {
+            font_size: settings.font_size(1.0),
+            color: Color::NONE,
+            ..Default::default()
+        };
+        let letter = commands
+            .spawn((
+                MatrixLetter::default(),
+                Text2dBundle {
+                    text: Text::from_section(' ', text_style),
+                    visibility: Visibility::Hidden,
+                    ..Default::default()
+                },
+                LetterDeath::default(),
+                LetterPhase::Pooled,
+            ))
+            .id();
+        pool.free.push(letter);
+    }
+}
+
+fn pointer_highlight(
+    mut query: Query<(
+        &mut MatrixLetter,
+        &GlobalTransform,
+        &LetterPhase,
+        Option<&Parent>,
+    )>,
+    strips: Query<&Parent, With<MatrixStrip>>,
+    pointer: Option<Res<MatrixPointer>>,
+) {
+    let Some(pointer) = pointer else {
+        return;
+    };
+    for (mut letter, transform, phase, parent) in &mut query {
+        if *phase == LetterPhase::Pooled {
+            continue;
+        }
+        let field = parent
+            .and_then(|strip| strips.get(strip.get()).ok())
+            .map(|field| field.get());
+        let highlight = pointer.falloff(field, transform.translation().truncate());
+        if letter.highlight != highlight {
+            letter.highlight = highlight;
+        }
+    }
+}
+
+pub(crate) fn update_color(
+    mut query: Query<(&mut Text, &MatrixLetter, &GlobalTransform), Changed<MatrixLetter>>,
+    pointer: Option<Res<MatrixPointer>>,
+    fog: Option<Res<MatrixFog>>,
+) {
+    let brightness = pointer.map_or(0.0, |p| p.brightness);
+    for (mut text, letter, transform) in &mut query {
+        let color = letter.text_color(1.0 + brightness * letter.highlight);
+        text.sections[0].style.color = match &fog {
+            Some(fog) => fog.apply(color, transform.translation().z),
+            None => color,
+        };
     }
 }
 
 impl Plugin for MatrixLetterPlugin {
     fn build(&self, app: &mut App) {
-        let asset_server = app.world().get_resource::<AssetServer>().unwrap();
-        let font = asset_server.load("fonts/matrix.ttf");
-
-        app.insert_resource(MatrixLetterData {
-            font,
-            font_size: 64.0,
-        })
-        .add_systems(Update, change_text)
-        .add_systems(Update, spawn_request_handler)
-        .add_systems(Update, letter_death)
-        .add_systems(Update, update_color)
-        .add_systems(Update, letter_despawn)
-        .add_systems(Update, component_animator_system::<MatrixLetter>);
+        app.init_resource::<MatrixLetterSettings>()
+            .init_resource::<MatrixGlyphs>()
+            .init_resource::<MatrixLetterPool>()
+            // The color fade animator runs here, with or without the
+            // `TweeningPlugin`.
+            .add_event::<TweenCompleted>()
+            .add_event::<LetterSpawned>()
+            .add_event::<LetterDied>()
+            .register_type::<MatrixLetter>()
+            .register_type::<MatrixLetterSpawnRequest>()
+            .register_type::<MatrixLetterSettings>()
+            .register_type::<LetterDeath>()
+            .register_type::<LetterPhase>()
+            .register_type::<MatrixLetterPool>()
+            .register_type::<MatrixGlyphs>()
+            .add_systems(Startup, warm_up_pool)
+            .add_systems(PreUpdate, resolve_glyphs)
+            .add_systems(Update, timed("change_text", change_text))
+            .add_systems(Update, timed("letter_requests", spawn_request_handler))
+            .add_systems(Update, restore_letter_text)
+            .add_systems(
+                Update,
+                (
+                    timed("letter_death", letter_death),
+                    letter_dying,
+                    letter_despawn,
+                )
+                    .chain(),
+            )
+            .add_systems(Update, pointer_highlight.before(update_color))
+            .add_systems(Update, timed("update_color", update_color))
+            .add_systems(
+                Update,
+                timed("animators", component_animator_system::<MatrixLetter>),
+            );
     }
 }
@@ -0,0 +1,473 @@
+use std::ops::{Range, RangeInclusive};
+
+use bevy::{core_pipeline::bloom::BloomSettings, prelude::*};
+use bevy_egui::{
+    egui::{self, Ui},
+    EguiContexts, EguiPlugin,
+};
+
+use crate::{
+    dof::MatrixDof,
+    matrix_field::{MatrixField, RainMode},
+    matrix_fog::MatrixFog,
+    matrix_glitch::{GlitchKind, MatrixGlitch, MatrixGlitches},
+    matrix_glyphs::{GlyphMix, MatrixGlyphs},
+    matrix_grading::{MatrixGrading, Tonemapper, LOOKS},
+    matrix_letter::MatrixLetterSettings,
+    matrix_preset::MatrixPreset,
+    matrix_strip::{FlipCurve, MatrixStripSettings},
+    post::{CrtMask, MatrixPost},
+};
+
+/// State of the in-app settings panel, toggled with `toggle_key`.
+#[derive(Resource)]
+pub struct MatrixPanel {
+    pub open: bool,
+    pub toggle_key: KeyCode,
+    pub preset_path: String,
+    status: String,
+}
+
+impl Default for MatrixPanel {
+    fn default() -> Self {
+        Self {
+            open: false,
+            toggle_key: KeyCode::F1,
+            preset_path: "presets/matrix.ron".to_string(),
+            status: String::new(),
+        }
+    }
+}
+
+#[derive(Event, Clone, Copy)]
+enum PresetRequest {
+    Save,
+    Load,
+}
+
+pub struct MatrixPanelPlugin;
+
+fn slider(ui: &mut Ui, label: &str, value: &mut f32, range: RangeInclusive<f32>) -> bool {
+    ui.add(egui::Slider::new(value, range).text(label))
+        .changed()
+}
+
+fn range_sliders(
+    ui: &mut Ui,
+    label: &str,
+    value: &mut Range<f32>,
+    range: RangeInclusive<f32>,
+) -> bool {
+    let (mut start, mut end) = (value.start, value.end);
+    let changed = slider(ui, &format!("{label} min"), &mut start, range.clone())
+        | slider(ui, &format!("{label} max"), &mut end, range);
+    if changed {
+        *value = ordered_range(start, end);
+    }
+    changed
+}
+
+/// `start..end`, swapped if needed and never empty. Spawners panic on empty
+/// ranges, and a fixed epsilon vanishes next to large bounds.
+fn ordered_range(start: f32, end: f32) -> Range<f32> {
+    let (start, end) = (start.min(end), start.max(end));
+    start..end.max(start + start.abs() * 1e-4 + 1e-4)
+}
+
+fn color(ui: &mut Ui, label: &str, value: &mut Color) -> bool {
+    let c = Srgba::from(*value);
+    let mut rgb = [c.red, c.green, c.blue];
+    let changed = ui
+        .horizontal(|ui| {
+            ui.label(label);
+            ui.color_edit_button_rgb(&mut rgb).changed()
+        })
+        .inner;
+    if changed {
+        *value = Color::srgb(rgb[0], rgb[1], rgb[2]);
+    }
+    changed
+}
+
+fn glyph_mix_label(glyphs: &MatrixGlyphs, mix: GlyphMix) -> String {
+    match mix {
+        GlyphMix::PerLetter => "mixed per letter".to_string(),
+        GlyphMix::PerStrip => "mixed per strip".to_string(),
+        GlyphMix::Only(set) => glyphs
+            .sets
+            .get(set)
+            .map_or_else(|| format!("missing set {set}"), |s| s.name.clone()),
+    }
+}
+
+fn field_ui(ui: &mut Ui, field: &mut MatrixField, glyphs: &MatrixGlyphs) -> bool {
+    let mut density = 1.0 / field.spawn_interval;
+    let mut max_length = field.max_length as f32;
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        ui.label("mode");
+        for (mode, label) in [
+            (RainMode::Entities, "entities"),
+            (RainMode::Shader, "shader"),
+        ] {
+            changed |= ui.selectable_value(&mut field.mode, mode, label).changed();
+        }
+    });
+    changed |= slider(ui, "density", &mut density, 1.0..=100.0);
+    changed |= slider(ui, "speed", &mut field.speed, -5.0..=5.0);
+    changed |= slider(ui, "max length", &mut max_length, 1.0..=100.0);
+    changed |= range_sliders(ui, "lifetime", &mut field.lifetime, 0.1..=10.0);
+    changed |= range_sliders(ui, "spawn rate", &mut field.spawnrate, 1.0..=60.0);
+    changed |= color(ui, "head", &mut field.palette.head);
+    changed |= color(ui, "body", &mut field.palette.body);
+    changed |= slider(
+        ui,
+        "head emission",
+        &mut field.palette.head_emission,
+        0.0..=16.0,
+    );
+    changed |= slider(
+        ui,
+        "body emission",
+        &mut field.palette.body_emission,
+        0.0..=4.0,
+    );
+    ui.horizontal(|ui| {
+        ui.label("flips");
+        for (label, curve) in [("film", FlipCurve::FILM), ("ambient", FlipCurve::AMBIENT)] {
+            if ui.button(label).clicked() {
+                field.flip = curve;
+                changed = true;
+            }
+        }
+    });
+    // Only existing sets can be picked.
+    egui::ComboBox::from_label("glyphs")
+        .selected_text(glyph_mix_label(glyphs, field.glyphs))
+        .show_ui(ui, |ui| {
+            let mixes = [GlyphMix::PerLetter, GlyphMix::PerStrip]
+                .into_iter()
+                .chain((0..glyphs.sets.len()).map(GlyphMix::Only));
+            for mix in mixes {
+                let label = glyph_mix_label(glyphs, mix);
+                changed |= ui.selectable_value(&mut field.glyphs, mix, label).changed();
+            }
+        });
+    changed |= slider(ui, "head flips", &mut field.flip.head, 0.0..=50.0);
+    changed |= slider(ui, "body flips", &mut field.flip.body, 0.0..=5.0);
+    changed |= slider(ui, "freeze age", &mut field.flip.freeze_age, 0.1..=30.0);
+    field.spawn_interval = 1.0 / density;
+    field.max_length = max_length.round() as u32;
+    changed
+}
+
+fn grading_ui(ui: &mut Ui, grading: &mut MatrixGrading) -> bool {
+    let mut changed = false;
+    egui::ComboBox::from_label("tonemapping")
+        .selected_text(format!("{:?}", grading.tonemapping))
+        .show_ui(ui, |ui| {
+            for tonemapper in Tonemapper::ALL {
+                changed |= ui
+                    .selectable_value(
+                        &mut grading.tonemapping,
+                        tonemapper,
+                        format!("{tonemapper:?}"),
+                    )
+                    .changed();
+            }
+        });
+    ui.horizontal_wrapped(|ui| {
+        ui.label("look");
+        changed |= ui
+            .selectable_value(&mut grading.lut, None, "none")
+            .changed();
+        for (name, path) in LOOKS {
+            changed |= ui
+                .selectable_value(&mut grading.lut, Some(path.to_string()), name)
+                .changed();
+        }
+    });
+    changed |= slider(ui, "look strength", &mut grading.lut_strength, 0.0..=1.0);
+    changed
+}
+
+fn crt_ui(ui: &mut Ui, post: &mut MatrixPost) -> bool {
+    let mut changed = ui.checkbox(&mut post.enabled, "enabled").changed();
+    changed |= slider(ui, "curvature", &mut post.curvature, -0.5..=0.5);
+    changed |= slider(ui, "corner radius", &mut post.corner_radius, 0.0..=0.3);
+    ui.horizontal(|ui| {
+        ui.label("mask");
+        for (label, mask) in [
+            ("none", CrtMask::None),
+            ("shadow mask", CrtMask::ShadowMask),
+            ("aperture grille", CrtMask::ApertureGrille),
+        ] {
+            changed |= ui.selectable_value(&mut post.mask, mask, label).changed();
+        }
+    });
+    changed |= slider(ui, "mask strength", &mut post.mask_strength, 0.0..=1.0);
+    changed |= slider(ui, "mask size", &mut post.mask_size, 1.0..=12.0);
+    changed |= slider(ui, "bar strength", &mut post.bar_strength, -0.5..=0.5);
+    changed |= slider(ui, "bar speed", &mut post.bar_speed, -1.0..=1.0);
+    changed |= slider(ui, "bar height", &mut post.bar_height, 0.01..=1.0);
+    changed |= slider(ui, "flicker", &mut post.flicker, 0.0..=0.3);
+    changed
+}
+
+fn toggle_panel(mut panel: ResMut<MatrixPanel>, keycode: Res<ButtonInput<KeyCode>>) {
+    if keycode.just_pressed(panel.toggle_key) {
+        panel.open = !panel.open;
+    }
+}
+
+fn panel_ui(
+    mut contexts: EguiContexts,
+    mut panel: ResMut<MatrixPanel>,
+    mut fields: Query<(Entity, &mut MatrixField, Option<&Name>)>,
+    (mut strip, mut letter, glyphs): (
+        ResMut<MatrixStripSettings>,
+        ResMut<MatrixLetterSettings>,
+        Res<MatrixGlyphs>,
+    ),
+    (mut blooms, fog): (
+        Query<(Entity, &mut BloomSettings)>,
+        Option<ResMut<MatrixFog>>,
+    ),
+    (mut dofs, mut gradings, mut posts): (
+        Query<(Entity, &mut MatrixDof)>,
+        Query<(Entity, &mut MatrixGrading)>,
+        Query<(Entity, &mut MatrixPost)>,
+    ),
+    (mut requests, mut glitch_events, glitches): (
+        EventWriter<PresetRequest>,
+        EventWriter<MatrixGlitch>,
+        Option<ResMut<MatrixGlitches>>,
+    ),
+) {
+    if !panel.open {
+        return;
+    }
+    let panel = &mut *panel;
+    egui::Window::new("Matrix").show(contexts.ctx_mut(), |ui| {
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for (index, (entity, mut field, name)) in fields.iter_mut().enumerate() {
+                let title = name.map_or_else(|| format!("Field {index}"), |n| n.to_string());
+                egui::CollapsingHeader::new(title)
+                    .id_source(entity)
+                    .show(ui, |ui| {
+                        let mut edit = field.bypass_change_detection().clone();
+                        if field_ui(ui, &mut edit, &glyphs) {
+                            *field = edit;
+                        }
+                    });
+            }
+
+            ui.collapsing("Strip", |ui| {
+                let mut edit = strip.bypass_change_detection().clone();
+                let mut changed = slider(ui, "drift x", &mut edit.drift.x, -5.0..=5.0);
+                changed |= slider(ui, "drift y", &mut edit.drift.y, -5.0..=5.0);
+                changed |= slider(ui, "fade time", &mut edit.fade_time, 0.01..=2.0);
+                changed |= slider(ui, "cell size", &mut edit.cell_size, 0.1..=4.0);
+                if changed {
+                    *strip = edit;
+                }
+            });
+
+            ui.collapsing("Letter", |ui| {
+                let mut edit = letter.bypass_change_detection().clone();
+                let mut changed = slider(ui, "flip interval", &mut edit.flip_interval, 0.05..=10.0);
+                changed |= slider(ui, "shrink time", &mut edit.shrink_time, 0.01..=2.0);
+                changed |= slider(
+                    ui,
+                    "pixels per unit",
+                    &mut edit.pixels_per_unit,
+                    8.0..=256.0,
+                );
+                changed |= slider(ui, "mirrored", &mut edit.mirror_chance, 0.0..=1.0);
+                changed |= slider(ui, "rotated", &mut edit.rotate_chance, 0.0..=1.0);
+                changed |= slider(ui, "upside down", &mut edit.upside_down_chance, 0.0..=1.0);
+                if changed {
+                    *letter = edit;
+                }
+            });
+
+            if let Some(mut fog) = fog {
+                ui.collapsing("Fog", |ui| {
+                    let mut edit = fog.bypass_change_detection().clone();
+                    let mut changed = color(ui, "color", &mut edit.color);
+                    changed |= slider(ui, "near", &mut edit.near, -10.0..=5.0);
+                    changed |= slider(ui, "far", &mut edit.far, -10.0..=5.0);
+                    changed |= slider(ui, "falloff", &mut edit.falloff, 0.1..=4.0);
+                    changed |= slider(ui, "strength", &mut edit.strength, 0.0..=1.0);
+                    changed |= slider(ui, "desaturation", &mut edit.desaturation, 0.0..=1.0);
+                    if changed {
+                        *fog = edit;
+                    }
+                });
+            }
+
+            for (entity, mut bloom) in &mut blooms {
+                egui::CollapsingHeader::new("Bloom")
+                    .id_source(entity)
+                    .show(ui, |ui| {
+                        let mut edit = bloom.bypass_change_detection().clone();
+                        let mut changed = slider(ui, "intensity", &mut edit.intensity, 0.0..=2.0);
+                        changed |= slider(
+                            ui,
+                            "threshold",
+                            &mut edit.prefilter_settings.threshold,
+                            0.0..=8.0,
+                        );
+                        changed |= slider(
+                            ui,
+                            "softness",
+                            &mut edit.prefilter_settings.threshold_softness,
+                            0.0..=1.0,
+                        );
+                        if changed {
+                            *bloom = edit;
+                        }
+                    });
+            }
+
+            for (entity, mut dof) in &mut dofs {
+                egui::CollapsingHeader::new("Depth of field")
+                    .id_source(entity)
+                    .show(ui, |ui| {
+                        let mut edit = dof.bypass_change_detection().clone();
+                        let mut changed = slider(
+                            ui,
+                            "focal depth",
+                            &mut edit.focal_depth,
+                            edit.far..=edit.near,
+                        );
+                        changed |= slider(ui, "aperture", &mut edit.aperture, 0.0..=0.02);
+                        changed |= slider(ui, "max blur", &mut edit.max_blur, 0.0..=0.1);
+                        if changed {
+                            *dof = edit;
+                        }
+                    });
+            }
+
+            ui.collapsing("Post", |ui| {
+                for (entity, mut grading) in &mut gradings {
+                    egui::CollapsingHeader::new("Grading")
+                        .id_source(entity)
+                        .show(ui, |ui| {
+                            let mut edit = grading.bypass_change_detection().clone();
+                            if grading_ui(ui, &mut edit) {
+                                *grading = edit;
+                            }
+                        });
+                }
+
+                for (entity, mut post) in &mut posts {
+                    egui::CollapsingHeader::new("CRT")
+                        .id_source(entity)
+                        .show(ui, |ui| {
+                            let mut edit = post.bypass_change_detection().clone();
+                            if crt_ui(ui, &mut edit) {
+                                *post = edit;
+                            }
+                        });
+                }
+
+                if let Some(mut glitches) = glitches {
+                    let shown = !posts.is_empty();
+                    ui.collapsing("Glitch", |ui| {
+                        if !shown {
+                            ui.label("Shows on cameras with a CRT, enabled or not.");
+                        }
+                        ui.horizontal_wrapped(|ui| {
+                            for kind in GlitchKind::ALL {
+                                if ui.button(format!("{kind:?}")).clicked() {
+                                    glitch_events.send(MatrixGlitch::new(kind, 0.5));
+                                }
+                            }
+                        });
+                        let mut random = glitches.random_interval.is_some();
+                        let mut interval = glitches.random_interval.unwrap_or(5.0);
+                        let mut changed = ui.checkbox(&mut random, "random").changed();
+                        if random {
+                            changed |= slider(ui, "interval", &mut interval, 0.5..=60.0);
+                        }
+                        if changed {
+                            glitches.random_interval = random.then_some(interval);
+                        }
+                    });
+                }
+            });
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label("Preset");
+                ui.text_edit_singleline(&mut panel.preset_path);
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Save").clicked() {
+                    requests.send(PresetRequest::Save);
+                }
+                if ui.button("Load").clicked() {
+                    requests.send(PresetRequest::Load);
+                }
+            });
+            if !panel.status.is_empty() {
+                ui.label(&panel.status);
+            }
+        });
+    });
+}
+
+fn handle_preset_requests(world: &mut World) {
+    let requests: Vec<PresetRequest> = world
+        .resource_mut::<Events<PresetRequest>>()
+        .drain()
+        .collect();
+    for request in requests {
+        let path = world.resource::<MatrixPanel>().preset_path.clone();
+        let status = match request {
+            PresetRequest::Save => MatrixPreset::capture(world)
+                .save(&path)
+                .map(|()| format!("Saved {path}")),
+            PresetRequest::Load => MatrixPreset::load(&path)
+                .and_then(|preset| preset.apply(world))
+                .map(|problems| {
+                    std::iter::once(format!("Loaded {path}"))
+                        .chain(problems)
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                })
+                .map_err(|e| format!("{path}: {e}")),
+        };
+        world.resource_mut::<MatrixPanel>().status = status.unwrap_or_else(|e| e);
+    }
+}
+
+impl Plugin for MatrixPanelPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin);
+        }
+        app.init_resource::<MatrixPanel>()
+            .add_event::<PresetRequest>()
+            .add_systems(
+                Update,
+                (toggle_panel, panel_ui, handle_preset_requests).chain(),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_never_empty() {
+        for (start, end) in [(10.0, 10.0), (60.0, 60.0), (0.1, 0.1), (5.0, 2.0)] {
+            let range = ordered_range(start, end);
+            assert!(range.start < range.end, "{range:?} from {start}..{end}");
+            assert_eq!(range.start, f32::min(start, end));
+        }
+        assert_eq!(ordered_range(1.0, 3.0), 1.0..3.0);
+    }
+}
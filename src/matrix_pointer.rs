@@ -0,0 +1,119 @@
+use bevy::{input::InputSystem, prelude::*, window::PrimaryWindow};
+
+use crate::matrix_texture::{MatrixTexture, MatrixTextureCamera};
+
+/// Mouse or touch position in world space, and how strongly it disturbs the
+/// rain around it.
+///
+/// Strips within `radius` are pushed away from the pointer (or pulled
+/// towards it for a negative `strength`), and letters close to it are
+/// brightened and flip their glyphs faster.
+///
+/// `position` is projected through the [`MatrixPointerCamera`]. Fields
+/// drawn into a [`MatrixTexture`] are seen through their own camera, so
+/// they use [`MatrixTexture::pointer`] instead, see
+/// [`MatrixPointer::position_in`].
+#[derive(Resource, Reflect, Clone)]
+#[reflect(Resource, Default)]
+pub struct MatrixPointer {
+    pub position: Option<Vec2>,
+    pub radius: f32,
+    /// Speed in world units per second at the pointer. Negative attracts.
+    pub strength: f32,
+    /// Extra brightness at the pointer, 1.0 doubles it.
+    pub brightness: f32,
+    /// Glyph flip rate multiplier at the pointer.
+    pub scramble: f32,
+    /// Pointer positions in the world space of texture fields.
+    #[reflect(ignore)]
+    textures: Vec<(Entity, Option<Vec2>)>,
+}
+
+impl Default for MatrixPointer {
+    fn default() -> Self {
+        Self {
+            position: None,
+            radius: 3.0,
+            strength: 4.0,
+            brightness: 1.5,
+            scramble: 10.0,
+            textures: Vec::new(),
+        }
+    }
+}
+
+impl MatrixPointer {
+    /// The pointer in world space as seen by the rain of `field`.
+    pub fn position_in(&self, field: Option<Entity>) -> Option<Vec2> {
+        match self
+            .textures
+            .iter()
+            .find(|(texture, _)| Some(*texture) == field)
+        {
+            Some((_, position)) => *position,
+            None => self.position,
+        }
+    }
+
+    /// Influence of the pointer on the rain of `field` at `point`, 1.0 at
+    /// the pointer and fading smoothly to 0.0 at `radius`.
+    pub fn falloff(&self, field: Option<Entity>, point: Vec2) -> f32 {
+        let Some(position) = self.position_in(field) else {
+            return 0.0;
+        };
+        let t = (1.0 - position.distance(point) / self.radius).max(0.0);
+        t * t
+    }
+}
+
+/// Marks the camera the pointer is projected through.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct MatrixPointerCamera;
+
+pub struct MatrixPointerPlugin;
+
+fn track_pointer(
+    mut pointer: ResMut<MatrixPointer>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    touches: Res<Touches>,
+    cameras: Query<(&Camera, &GlobalTransform), With<MatrixPointerCamera>>,
+    textures: Query<(Entity, &MatrixTexture, &Children)>,
+    texture_cameras: Query<(&Camera, &GlobalTransform), With<MatrixTextureCamera>>,
+) {
+    if let Ok((camera, transform)) = cameras.get_single() {
+        let cursor = touches
+            .iter()
+            .next()
+            .map(|touch| touch.position())
+            .or_else(|| windows.get_single().ok()?.cursor_position());
+        let position = cursor.and_then(|cursor| camera.viewport_to_world_2d(transform, cursor));
+        if pointer.position != position {
+            pointer.position = position;
+        }
+    }
+
+    let positions: Vec<_> = textures
+        .iter()
+        .map(|(entity, texture, children)| {
+            let position = texture.pointer.and_then(|uv| {
+                let (camera, transform) = texture_cameras.iter_many(children).next()?;
+                let size = camera.logical_viewport_size()?;
+                camera.viewport_to_world_2d(transform, uv * size)
+            });
+            (entity, position)
+        })
+        .collect();
+    if pointer.textures != positions {
+        pointer.textures = positions;
+    }
+}
+
+impl Plugin for MatrixPointerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MatrixPointer>()
+            .register_type::<MatrixPointer>()
+            .register_type::<MatrixPointerCamera>()
+            .add_systems(PreUpdate, track_pointer.after(InputSystem));
+    }
+}
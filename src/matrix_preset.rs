@@ -0,0 +1,396 @@
+use std::{fs, path::Path};
+
+use bevy::{core_pipeline::bloom::BloomSettings, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    dof::MatrixDof,
+    matrix_field::{MatrixField, RainMode},
+    matrix_fog::MatrixFog,
+    matrix_glyphs::{GlyphMix, GlyphSet, MatrixGlyphs},
+    matrix_grading::MatrixGrading,
+    matrix_letter::MatrixLetterSettings,
+    matrix_strip::{DepthCurve, FlipCurve, MatrixPalette, MatrixStripSettings},
+    post::MatrixPost,
+};
+
+fn to_rgb(color: Color) -> [f32; 3] {
+    let c = Srgba::from(color);
+    [c.red, c.green, c.blue]
+}
+
+fn from_rgb([r, g, b]: [f32; 3]) -> Color {
+    Color::srgb(r, g, b)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FieldPreset {
+    /// Matches the field's `Name`. Unnamed presets are matched by order.
+    pub name: Option<String>,
+    pub spawn_interval: f32,
+    pub speed: f32,
+    pub max_length: u32,
+    pub lifetime: [f32; 2],
+    pub spawnrate: [f32; 2],
+    pub head: [f32; 3],
+    pub body: [f32; 3],
+    #[serde(default)]
+    pub head_emission: Option<f32>,
+    #[serde(default)]
+    pub body_emission: Option<f32>,
+    #[serde(default)]
+    pub flip: Option<FlipCurve>,
+    #[serde(default)]
+    pub glyphs: Option<GlyphMix>,
+    #[serde(default)]
+    pub mode: Option<RainMode>,
+}
+
+impl FieldPreset {
+    fn capture(field: &MatrixField, name: Option<&Name>) -> Self {
+        Self {
+            name: name.map(|n| n.to_string()),
+            spawn_interval: field.spawn_interval,
+            speed: field.speed,
+            max_length: field.max_length,
+            lifetime: [field.lifetime.start, field.lifetime.end],
+            spawnrate: [field.spawnrate.start, field.spawnrate.end],
+            head: to_rgb(field.palette.head),
+            body: to_rgb(field.palette.body),
+            head_emission: Some(field.palette.head_emission),
+            body_emission: Some(field.palette.body_emission),
+            flip: Some(field.flip),
+            glyphs: Some(field.glyphs),
+            mode: Some(field.mode),
+        }
+    }
+
+    /// Checks the values the strip spawner can't work with, the way the
+    /// control server checks them.
+    fn check(&self) -> Result<(), String> {
+        positive("spawn_interval", self.spawn_interval)?;
+        range("lifetime", self.lifetime)?;
+        range("spawnrate", self.spawnrate)?;
+        if self.max_length == 0 {
+            return Err("max_length: expected a whole number of at least 1, got 0".into());
+        }
+        Ok(())
+    }
+
+    fn apply(&self, field: &mut MatrixField) -> Result<(), String> {
+        self.check()?;
+        field.spawn_interval = self.spawn_interval;
+        field.speed = self.speed;
+        field.max_length = self.max_length;
+        field.lifetime = self.lifetime[0]..self.lifetime[1];
+        field.spawnrate = self.spawnrate[0]..self.spawnrate[1];
+        field.palette = MatrixPalette {
+            head: from_rgb(self.head),
+            body: from_rgb(self.body),
+            ..field.palette
+        };
+        if let Some(emission) = self.head_emission {
+            field.palette.head_emission = emission;
+        }
+        if let Some(emission) = self.body_emission {
+            field.palette.body_emission = emission;
+        }
+        if let Some(flip) = self.flip {
+            field.flip = flip;
+        }
+        if let Some(glyphs) = self.glyphs {
+            field.glyphs = glyphs;
+        }
+        if let Some(mode) = self.mode {
+            field.mode = mode;
+        }
+        Ok(())
+    }
+}
+
+fn positive(key: &str, value: f32) -> Result<f32, String> {
+    match value {
+        v if v > 0.0 => Ok(v),
+        v => Err(format!("{key}: expected a positive number, got {v}")),
+    }
+}
+
+fn range(key: &str, [min, max]: [f32; 2]) -> Result<(), String> {
+    let (min, max) = (positive(key, min)?, positive(key, max)?);
+    if min < max {
+        Ok(())
+    } else {
+        Err(format!("{key}: expected min < max, got [{min}, {max}]"))
+    }
+}
+
+/// Bloom of the HDR camera. The presets are calibrated against the default
+/// [`MatrixPalette`](crate::matrix_strip::MatrixPalette) emissions: heads
+/// are above the threshold, bodies below it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BloomPreset {
+    pub intensity: f32,
+    pub threshold: f32,
+    pub threshold_softness: f32,
+}
+
+impl BloomPreset {
+    /// Only heads glow.
+    pub const HEADS: Self = Self {
+        intensity: 0.3,
+        threshold: 1.0,
+        threshold_softness: 0.5,
+    };
+
+    /// Heads glow strongly, bright bodies close to the camera a little.
+    pub const FILM: Self = Self {
+        intensity: 0.5,
+        threshold: 0.6,
+        threshold_softness: 0.6,
+    };
+
+    pub fn settings(&self) -> BloomSettings {
+        let mut bloom = BloomSettings::default();
+        self.apply(&mut bloom);
+        bloom
+    }
+
+    pub fn apply(&self, bloom: &mut BloomSettings) {
+        bloom.intensity = self.intensity;
+        bloom.prefilter_settings.threshold = self.threshold;
+        bloom.prefilter_settings.threshold_softness = self.threshold_softness;
+    }
+}
+
+impl Default for BloomPreset {
+    fn default() -> Self {
+        Self::HEADS
+    }
+}
+
+fn glyph_problems(world: &mut World) -> Vec<String> {
+    if !world.contains_resource::<MatrixGlyphs>() {
+        return Vec::new();
+    }
+    let mut fields = world.query::<(&MatrixField, Option<&Name>)>();
+    let glyphs = world.resource::<MatrixGlyphs>();
+    let mut problems: Vec<String> = fields
+        .iter(world)
+        .enumerate()
+        .filter_map(|(index, (field, name))| {
+            let e = glyphs.check_mix(field.glyphs).err()?;
+            Some(match name {
+                Some(name) => format!("Field \"{name}\": {e}"),
+                None => format!("Field {index}: {e}"),
+            })
+        })
+        .collect();
+    // Sets with loaded fonts are checked right away, the others once their
+    // fonts are in.
+    if world.contains_resource::<AssetServer>() && world.contains_resource::<Assets<Font>>() {
+        world.resource_scope(|world, mut glyphs: Mut<MatrixGlyphs>| {
+            let reports = glyphs.resolve(world.resource(), world.resource());
+            for report in &reports {
+                warn!("{report}");
+            }
+            problems.extend(reports);
+        });
+    }
+    problems
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DofPreset {
+    pub focal_depth: f32,
+    pub aperture: f32,
+    pub max_blur: f32,
+}
+
+/// Every tunable rain parameter, as stored in a preset file.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct MatrixPreset {
+    pub fields: Vec<FieldPreset>,
+    pub drift: Option<[f32; 2]>,
+    pub fade_time: Option<f32>,
+    pub cell_size: Option<f32>,
+    pub size_curve: Option<DepthCurve>,
+    pub brightness_curve: Option<DepthCurve>,
+    pub pixels_per_unit: Option<f32>,
+    pub flip_interval: Option<f32>,
+    pub shrink_time: Option<f32>,
+    pub mirror_chance: Option<f32>,
+    pub rotate_chance: Option<f32>,
+    pub upside_down_chance: Option<f32>,
+    pub glyph_sets: Option<Vec<GlyphSet>>,
+    pub fog: Option<MatrixFog>,
+    pub bloom: Option<BloomPreset>,
+    pub dof: Option<DofPreset>,
+    pub grading: Option<MatrixGrading>,
+    pub crt: Option<MatrixPost>,
+}
+
+impl MatrixPreset {
+    /// Reads the current settings from the world.
+    pub fn capture(world: &mut World) -> Self {
+        let mut preset = Self::default();
+        let mut fields = world.query::<(&MatrixField, Option<&Name>)>();
+        for (field, name) in fields.iter(world) {
+            preset.fields.push(FieldPreset::capture(field, name));
+        }
+        if let Some(strip) = world.get_resource::<MatrixStripSettings>() {
+            preset.drift = Some(strip.drift.into());
+            preset.fade_time = Some(strip.fade_time);
+            preset.cell_size = Some(strip.cell_size);
+            preset.size_curve = Some(strip.size_curve);
+            preset.brightness_curve = Some(strip.brightness_curve);
+        }
+        if let Some(letter) = world.get_resource::<MatrixLetterSettings>() {
+            preset.flip_interval = Some(letter.flip_interval);
+            preset.shrink_time = Some(letter.shrink_time);
+            preset.pixels_per_unit = Some(letter.pixels_per_unit);
+            preset.mirror_chance = Some(letter.mirror_chance);
+            preset.rotate_chance = Some(letter.rotate_chance);
+            preset.upside_down_chance = Some(letter.upside_down_chance);
+        }
+        preset.glyph_sets = world
+            .get_resource::<MatrixGlyphs>()
+            .map(|glyphs| glyphs.sets.clone());
+        preset.fog = world.get_resource::<MatrixFog>().cloned();
+        let mut blooms = world.query::<&BloomSettings>();
+        preset.bloom = blooms.iter(world).next().map(|bloom| BloomPreset {
+            intensity: bloom.intensity,
+            threshold: bloom.prefilter_settings.threshold,
+            threshold_softness: bloom.prefilter_settings.threshold_softness,
+        });
+        let mut dofs = world.query::<&MatrixDof>();
+        preset.dof = dofs.iter(world).next().map(|dof| DofPreset {
+            focal_depth: dof.focal_depth,
+            aperture: dof.aperture,
+            max_blur: dof.max_blur,
+        });
+        let mut gradings = world.query::<&MatrixGrading>();
+        preset.grading = gradings.iter(world).next().cloned();
+        let mut posts = world.query::<&MatrixPost>();
+        preset.crt = posts.iter(world).next().cloned();
+        preset
+    }
+
+    /// Writes the settings into the world. Missing entries are left alone.
+    ///
+    /// A preset with field values the spawner can't work with, such as an
+    /// empty lifetime range, is rejected as a whole. Otherwise returns what
+    /// is wrong with the preset's glyphs: fields drawing from sets that
+    /// don't exist, and characters no font of a set covers, as far as the
+    /// fonts are loaded already.
+    pub fn apply(&self, world: &mut World) -> Result<Vec<String>, String> {
+        for (index, field) in self.fields.iter().enumerate() {
+            field.check().map_err(|e| match &field.name {
+                Some(name) => format!("Field \"{name}\": {e}"),
+                None => format!("Field {index}: {e}"),
+            })?;
+        }
+        let mut fields = world.query::<(&mut MatrixField, Option<&Name>)>();
+        let mut unnamed = self.fields.iter().filter(|f| f.name.is_none());
+        for (mut field, name) in fields.iter_mut(world) {
+            let preset = match name {
+                Some(name) => self
+                    .fields
+                    .iter()
+                    .find(|f| f.name.as_deref() == Some(name.as_str())),
+                None => unnamed.next(),
+            };
+            if let Some(preset) = preset {
+                preset.apply(&mut field)?;
+            }
+        }
+        if let Some(mut strip) = world.get_resource_mut::<MatrixStripSettings>() {
+            if let Some(drift) = self.drift {
+                strip.drift = drift.into();
+            }
+            if let Some(fade_time) = self.fade_time {
+                strip.fade_time = fade_time;
+            }
+            if let Some(cell_size) = self.cell_size {
+                strip.cell_size = cell_size;
+            }
+            if let Some(size_curve) = self.size_curve {
+                strip.size_curve = size_curve;
+            }
+            if let Some(brightness_curve) = self.brightness_curve {
+                strip.brightness_curve = brightness_curve;
+            }
+        }
+        if let Some(mut letter) = world.get_resource_mut::<MatrixLetterSettings>() {
+            if let Some(flip_interval) = self.flip_interval {
+                letter.flip_interval = flip_interval;
+            }
+            if let Some(shrink_time) = self.shrink_time {
+                letter.shrink_time = shrink_time;
+            }
+            if let Some(pixels_per_unit) = self.pixels_per_unit {
+                letter.pixels_per_unit = pixels_per_unit;
+            }
+            if let Some(mirror_chance) = self.mirror_chance {
+                letter.mirror_chance = mirror_chance;
+            }
+            if let Some(rotate_chance) = self.rotate_chance {
+                letter.rotate_chance = rotate_chance;
+            }
+            if let Some(upside_down_chance) = self.upside_down_chance {
+                letter.upside_down_chance = upside_down_chance;
+            }
+        }
+        if let Some(sets) = &self.glyph_sets {
+            if let Some(mut glyphs) = world.get_resource_mut::<MatrixGlyphs>() {
+                glyphs.sets = sets.clone();
+            }
+        }
+        if let Some(fog) = &self.fog {
+            if let Some(mut current) = world.get_resource_mut::<MatrixFog>() {
+                *current = fog.clone();
+            }
+        }
+        if let Some(preset) = &self.bloom {
+            let mut blooms = world.query::<&mut BloomSettings>();
+            for mut bloom in blooms.iter_mut(world) {
+                preset.apply(&mut bloom);
+            }
+        }
+        if let Some(preset) = &self.dof {
+            let mut dofs = world.query::<&mut MatrixDof>();
+            for mut dof in dofs.iter_mut(world) {
+                dof.focal_depth = preset.focal_depth;
+                dof.aperture = preset.aperture;
+                dof.max_blur = preset.max_blur;
+            }
+        }
+        if let Some(preset) = &self.grading {
+            let mut gradings = world.query::<&mut MatrixGrading>();
+            for mut grading in gradings.iter_mut(world) {
+                *grading = preset.clone();
+            }
+        }
+        if let Some(preset) = &self.crt {
+            let mut posts = world.query::<&mut MatrixPost>();
+            for mut post in posts.iter_mut(world) {
+                *post = preset.clone();
+            }
+        }
+        Ok(glyph_problems(world))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| e.to_string())?;
+        if let Some(dir) = path.as_ref().parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        fs::write(path, text).map_err(|e| e.to_string())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        ron::from_str(&text).map_err(|e| e.to_string())
+    }
+}
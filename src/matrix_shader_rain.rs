@@ -0,0 +1,396 @@
+use ab_glyph::{point, Font as _, PxScale, ScaleFont as _};
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::MeshVertexBufferLayoutRef,
+        render_asset::RenderAssetUsages,
+        render_resource::{
+            AsBindGroup, BlendState, Extent3d, RenderPipelineDescriptor, ShaderRef,
+            SpecializedMeshPipelineError, TextureDimension, TextureFormat,
+        },
+        view::RenderLayers,
+    },
+    sprite::{Material2d, Material2dKey, Material2dPlugin, MaterialMesh2dBundle, Mesh2dHandle},
+};
+
+use crate::{
+    matrix_field::{MatrixField, RainMode},
+    matrix_fog::MatrixFog,
+    matrix_glyphs::{GlyphMix, MatrixGlyphs},
+    matrix_letter::MatrixLetterSettings,
+    matrix_strip::MatrixStripSettings,
+};
+
+/// Depth layers the shader draws, spread over the field's `z` range.
+pub const SHADER_RAIN_LAYERS: usize = 3;
+const PARAMS: usize = 6 + 3 * SHADER_RAIN_LAYERS;
+/// Size of a glyph in the atlas, in pixels.
+const GLYPH_PIXELS: u32 = 64;
+
+/// Draws the rain of a [`RainMode::Shader`] field, see `shader_rain.wgsl`
+/// for the layout of `params`.
+#[derive(Asset, TypePath, AsBindGroup, Clone)]
+pub struct ShaderRainMaterial {
+    #[uniform(0)]
+    params: [Vec4; PARAMS],
+    #[texture(1)]
+    #[sampler(2)]
+    atlas: Handle<Image>,
+}
+
+impl Material2d for ShaderRainMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/shader_rain.wgsl".into()
+    }
+
+    fn specialize(
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayoutRef,
+        _key: Material2dKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        if let Some(target) = descriptor
+            .fragment
+            .as_mut()
+            .and_then(|fragment| fragment.targets[0].as_mut())
+        {
+            target.blend = Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING);
+        }
+        Ok(())
+    }
+}
+
+/// The quad drawing a shader rain field, a child of the field.
+#[derive(Component)]
+pub(crate) struct ShaderRainQuad;
+
+/// Added to fields in [`RainMode::Shader`].
+///
+/// Instead of strip and letter entities, every column of a depth layer
+/// runs one drop per cycle, with its start, speed, lifetime and glyphs
+/// hashed from the column and cycle. The cycles are as long as it takes
+/// the field's `spawn_interval` to spawn a strip in every column, and
+/// drops use the same letter rates, lifetimes, lengths, colors, flips and
+/// fades as strips would.
+///
+/// The drops are a function of the time only, so what changes the rain
+/// of entity fields from frame to frame doesn't reach them: the audio
+/// modulation, the [`crate::matrix_budget::RainBudget`] quality, and the
+/// depth of field layers, the quad stays on the layers of its field.
+#[derive(Component)]
+pub struct ShaderRain {
+    quad: Entity,
+    /// The mix the atlas was built for, `None` until the glyphs are
+    /// resolved.
+    atlas: Option<GlyphMix>,
+    /// Columns, rows and glyph count of the atlas.
+    grid: Vec3,
+}
+
+impl ShaderRain {
+    /// Field-space depth of `layer`, far to near.
+    pub fn layer_depth(field: &MatrixField, layer: usize) -> f32 {
+        let t = (layer as f32 + 0.5) / SHADER_RAIN_LAYERS as f32;
+        field.z.start + (field.z.end - field.z.start) * t
+    }
+
+    /// Field-space area covered by the rain: the field's width, from the
+    /// top of its spawn range down to where its longest strips end.
+    pub fn area(field: &MatrixField, settings: &MatrixStripSettings) -> Rect {
+        let cell = (0..SHADER_RAIN_LAYERS)
+            .map(|layer| {
+                settings.cell_size * settings.size_curve.at(Self::layer_depth(field, layer))
+            })
+            .fold(0.0, f32::max);
+        Rect::new(
+            field.x.start,
+            field.y.start - field.max_length as f32 * cell,
+            field.x.end,
+            field.y.end + cell,
+        )
+    }
+
+    /// The shader parameters of `field`, with fog as seen from a field at
+    /// world depth `z`. The shader takes the time from the view globals.
+    pub fn params(
+        &self,
+        field: &MatrixField,
+        (strip, letter): (&MatrixStripSettings, &MatrixLetterSettings),
+        fog: Option<&MatrixFog>,
+        z: f32,
+    ) -> [Vec4; PARAMS] {
+        let area = Self::area(field, strip);
+        let width = field.x.end - field.x.start;
+        let mut params = [Vec4::ZERO; PARAMS];
+        params[0] = Vec4::new(area.min.x, area.min.y, area.width(), area.height());
+        params[1] = Vec4::new(
+            strip.fade_time,
+            letter.shrink_time,
+            letter.flip_interval,
+            0.0,
+        );
+        params[2] = Vec4::new(
+            field.flip.head,
+            field.flip.body,
+            field.flip.freeze_age,
+            field.flip.falloff,
+        );
+        params[3] = Vec4::new(
+            field.spawnrate.start,
+            field.spawnrate.end,
+            field.lifetime.start,
+            field.lifetime.end,
+        );
+        params[4] = Vec4::new(
+            field.y.start,
+            field.y.end,
+            field.max_length as f32,
+            SHADER_RAIN_LAYERS as f32 * width * field.spawn_interval,
+        );
+        params[5] = self.grid.extend(0.0);
+        for layer in 0..SHADER_RAIN_LAYERS {
+            let depth = Self::layer_depth(field, layer);
+            let scale = strip.size_curve.at(depth);
+            let brightness = strip.brightness_curve.at(depth);
+            let drift = strip.drift * field.speed * scale;
+            let color = |color: Color, emission: f32| {
+                let color = letter_color(color, brightness, emission);
+                let color = fog.map_or(color, |fog| fog.apply(color, z + depth));
+                LinearRgba::from(color).to_vec4()
+            };
+            let row = 6 + 3 * layer;
+            params[row] = Vec4::new(drift.x, drift.y, strip.cell_size * scale, scale);
+            params[row + 1] = color(field.palette.head, field.palette.head_emission);
+            params[row + 2] = color(field.palette.body, field.palette.body_emission);
+        }
+        params
+    }
+}
+
+/// The color of a letter lit by `brightness`, as strips give it, before fog.
+fn letter_color(color: Color, brightness: f32, emission: f32) -> Color {
+    let color = Srgba::from(color);
+    let lit = Srgba::new(
+        color.red * brightness,
+        color.green * brightness,
+        color.blue * brightness,
+        color.alpha,
+    );
+    let linear = LinearRgba::from(lit);
+    Color::from(LinearRgba::new(
+        linear.red * emission,
+        linear.green * emission,
+        linear.blue * emission,
+        linear.alpha,
+    ))
+}
+
+/// Rasterizes the glyphs `mix` draws from into a grid of coverage tiles.
+/// `None` until the sets and their fonts are resolved.
+fn glyph_atlas(
+    glyphs: &MatrixGlyphs,
+    mix: GlyphMix,
+    fonts: &Assets<Font>,
+) -> Option<(Image, Vec3)> {
+    if !glyphs.is_ready() {
+        return None;
+    }
+    let sets = match mix {
+        GlyphMix::Only(set) => set..set + 1,
+        GlyphMix::PerLetter | GlyphMix::PerStrip => 0..glyphs.sets.len(),
+    };
+    let mut tiles = Vec::new();
+    for set in sets {
+        for (glyph, font) in glyphs.resolved(set).into_iter().flatten() {
+            if let Some(font) = fonts.get(font) {
+                tiles.push((glyph, font.font.clone()));
+            }
+        }
+    }
+
+    let count = tiles.len() as u32;
+    let columns = (count as f32).sqrt().ceil().max(1.0) as u32;
+    let rows = count.div_ceil(columns).max(1);
+    let size = UVec2::new(columns, rows) * GLYPH_PIXELS;
+    let mut data = vec![0; (size.x * size.y) as usize];
+    let scale = PxScale::from(GLYPH_PIXELS as f32);
+    for (index, (glyph, font)) in tiles.iter().enumerate() {
+        let tile = UVec2::new(index as u32 % columns, index as u32 / columns) * GLYPH_PIXELS;
+        let scaled = font.as_scaled(scale);
+        let id = font.glyph_id(*glyph);
+        // Centered in the tile, on a baseline shared by all glyphs.
+        let x = (GLYPH_PIXELS as f32 - scaled.h_advance(id)) / 2.0;
+        let y = (GLYPH_PIXELS as f32 - scaled.height()) / 2.0 + scaled.ascent();
+        let Some(outline) = font.outline_glyph(id.with_scale_and_position(scale, point(x, y)))
+        else {
+            continue;
+        };
+        let bounds = outline.px_bounds();
+        outline.draw(|gx, gy, coverage| {
+            let px = bounds.min.x as i32 + gx as i32;
+            let py = bounds.min.y as i32 + gy as i32;
+            if (0..GLYPH_PIXELS as i32).contains(&px) && (0..GLYPH_PIXELS as i32).contains(&py) {
+                let index = (tile.y + py as u32) * size.x + tile.x + px as u32;
+                data[index as usize] = (coverage.clamp(0.0, 1.0) * 255.0) as u8;
+            }
+        });
+    }
+
+    let image = Image::new(
+        Extent3d {
+            width: size.x,
+            height: size.y,
+            ..default()
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::R8Unorm,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    Some((image, Vec3::new(columns as f32, rows as f32, count as f32)))
+}
+
+fn empty_atlas() -> Image {
+    Image::new_fill(
+        Extent3d::default(),
+        TextureDimension::D2,
+        &[0],
+        TextureFormat::R8Unorm,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+}
+
+/// Spawns and despawns the quads of fields switching modes.
+fn switch_modes(
+    mut commands: Commands,
+    fields: Query<(Entity, &MatrixField, Option<&ShaderRain>), Changed<MatrixField>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ShaderRainMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    for (entity, field, rain) in &fields {
+        match (field.mode, rain) {
+            (RainMode::Shader, None) => {
+                let material = materials.add(ShaderRainMaterial {
+                    params: [Vec4::ZERO; PARAMS],
+                    atlas: images.add(empty_atlas()),
+                });
+                let quad = commands
+                    .spawn((
+                        MaterialMesh2dBundle {
+                            mesh: Mesh2dHandle(meshes.add(Rectangle::new(1.0, 1.0))),
+                            material,
+                            ..default()
+                        },
+                        ShaderRainQuad,
+                    ))
+                    .id();
+                commands
+                    .entity(entity)
+                    .insert(ShaderRain {
+                        quad,
+                        atlas: None,
+                        grid: Vec3::ZERO,
+                    })
+                    .add_child(quad);
+            }
+            (RainMode::Entities, Some(rain)) => {
+                commands.entity(rain.quad).despawn_recursive();
+                commands.entity(entity).remove::<ShaderRain>();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Keeps the quads over their fields, and their materials up to date.
+/// Materials are only touched when something they're made from changes.
+fn update_quads(
+    mut commands: Commands,
+    mut fields: Query<(
+        Ref<MatrixField>,
+        &mut ShaderRain,
+        Ref<GlobalTransform>,
+        Option<&RenderLayers>,
+    )>,
+    mut quads: Query<
+        (
+            &mut Transform,
+            &Handle<ShaderRainMaterial>,
+            Option<&RenderLayers>,
+        ),
+        With<ShaderRainQuad>,
+    >,
+    (mut materials, mut images, fonts): (
+        ResMut<Assets<ShaderRainMaterial>>,
+        ResMut<Assets<Image>>,
+        Res<Assets<Font>>,
+    ),
+    (strip, letter, glyphs, fog): (
+        Res<MatrixStripSettings>,
+        Res<MatrixLetterSettings>,
+        Res<MatrixGlyphs>,
+        Option<Res<MatrixFog>>,
+    ),
+    mut had_fog: Local<bool>,
+) {
+    let settings_changed = strip.is_changed()
+        || letter.is_changed()
+        || fog.as_ref().is_some_and(|fog| fog.is_changed())
+        || fog.is_some() != *had_fog;
+    *had_fog = fog.is_some();
+    for (field, mut rain, transform, layers) in &mut fields {
+        let Ok((mut quad, handle, quad_layers)) = quads.get_mut(rain.quad) else {
+            continue;
+        };
+        // Render layers aren't inherited, the quad has to carry the field's.
+        if layers != quad_layers {
+            match layers {
+                Some(layers) => commands.entity(rain.quad).insert(layers.clone()),
+                None => commands.entity(rain.quad).remove::<RenderLayers>(),
+            };
+        }
+        let area = ShaderRain::area(&field, &strip);
+        quad.set_if_neq(
+            Transform::from_translation(area.center().extend(field.z.start.min(field.z.end)))
+                .with_scale(area.size().extend(1.0)),
+        );
+
+        if glyphs.is_changed() {
+            rain.atlas = None;
+        }
+        let atlas = if rain.atlas != Some(field.glyphs) {
+            glyph_atlas(&glyphs, field.glyphs, &fonts)
+        } else {
+            None
+        };
+        if atlas.is_none() && !settings_changed && !field.is_changed() && !transform.is_changed() {
+            continue;
+        }
+        let Some(material) = materials.get_mut(handle) else {
+            continue;
+        };
+        if let Some((atlas, grid)) = atlas {
+            material.atlas = images.add(atlas);
+            rain.atlas = Some(field.glyphs);
+            rain.grid = grid;
+        }
+        material.params = rain.params(
+            &field,
+            (&strip, &letter),
+            fog.as_deref(),
+            transform.translation().z,
+        );
+    }
+}
+
+/// Draws fields in [`RainMode::Shader`] with [`ShaderRainMaterial`]. Needs
+/// the settings of the [`crate::matrix_strip::MatrixStripPlugin`] and
+/// [`crate::matrix_letter::MatrixLetterPlugin`].
+pub struct MatrixShaderRainPlugin;
+
+impl Plugin for MatrixShaderRainPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(Material2dPlugin::<ShaderRainMaterial>::default())
+            .add_systems(Update, (switch_modes, update_quads).chain());
+    }
+}
@@ -0,0 +1,228 @@
+use std::ops::Range;
+
+use bevy::{audio::Volume, prelude::*};
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{matrix_letter::LetterSpawned, utils::with_rng};
+
+/// Decides which sound cues get a voice.
+///
+/// At most `max_voices` cues play at once, each for `voice_length` seconds,
+/// and two cues are at least `min_interval` seconds apart. Cues requested
+/// in the same frame are served loudest first.
+#[derive(Clone, Debug)]
+pub struct VoiceLimiter {
+    pub max_voices: usize,
+    pub voice_length: f32,
+    pub min_interval: f32,
+    ends: Vec<f32>,
+    last: f32,
+}
+
+impl Default for VoiceLimiter {
+    fn default() -> Self {
+        Self::new(8, 0.1, 0.01)
+    }
+}
+
+impl VoiceLimiter {
+    pub fn new(max_voices: usize, voice_length: f32, min_interval: f32) -> Self {
+        Self {
+            max_voices,
+            voice_length,
+            min_interval,
+            ends: Vec::new(),
+            last: f32::NEG_INFINITY,
+        }
+    }
+
+    /// Number of voices still playing at `now`.
+    pub fn active(&self, now: f32) -> usize {
+        self.ends.iter().filter(|&&end| end > now).count()
+    }
+
+    /// Takes voices for cues requested together at `now`, given their
+    /// volumes. Returns the indices of the cues that play, loudest first.
+    pub fn allocate(&mut self, now: f32, volumes: &[f32]) -> Vec<usize> {
+        self.ends.retain(|&end| end > now);
+        if now - self.last < self.min_interval {
+            return Vec::new();
+        }
+        let mut order: Vec<usize> = (0..volumes.len()).collect();
+        order.sort_by(|&a, &b| volumes[b].total_cmp(&volumes[a]));
+        order.truncate(self.max_voices.saturating_sub(self.ends.len()));
+        if !order.is_empty() {
+            self.last = now;
+            let end = now + self.voice_length;
+            self.ends.resize(self.ends.len() + order.len(), end);
+        }
+        order
+    }
+}
+
+/// Click sounds played when new letters appear at the head of a strip.
+///
+/// Cues are panned by the letter's horizontal position on the
+/// [`MatrixSoundCamera`]'s screen and get quieter with the strip's depth,
+/// the same way letters get dimmer.
+#[derive(Resource, Clone)]
+pub struct MatrixSound {
+    pub samples: Vec<Handle<AudioSource>>,
+    pub volume: f32,
+    /// Playback speed range, picked at random for every cue.
+    pub pitch: Range<f32>,
+    pub limiter: VoiceLimiter,
+}
+
+impl MatrixSound {
+    pub fn new(samples: Vec<Handle<AudioSource>>) -> Self {
+        Self {
+            samples,
+            volume: 0.3,
+            pitch: 0.9..1.1,
+            limiter: VoiceLimiter::default(),
+        }
+    }
+}
+
+/// Marks the camera whose screen the sound cues are panned across.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct MatrixSoundCamera;
+
+/// Plays [`MatrixSound`] cues with `bevy_audio`. Not part of the default
+/// setup, add it to enable sound.
+pub struct MatrixSoundPlugin {
+    pub samples: Vec<String>,
+}
+
+impl Default for MatrixSoundPlugin {
+    fn default() -> Self {
+        Self {
+            samples: vec![
+                "sounds/click.wav".to_string(),
+                "sounds/tick.wav".to_string(),
+            ],
+        }
+    }
+}
+
+/// Cues are panned with spatial audio, which needs a listener. Runs after
+/// the app's startup systems, so a listener spawned there is kept.
+fn setup_listener(mut commands: Commands, listeners: Query<(), With<SpatialListener>>) {
+    if listeners.is_empty() {
+        // Ears close enough for the cues between them not to be attenuated.
+        commands.spawn((SpatialBundle::default(), SpatialListener::new(1.0)));
+    }
+}
+
+fn play_cues(
+    mut commands: Commands,
+    mut sound: ResMut<MatrixSound>,
+    mut spawned: EventReader<LetterSpawned>,
+    cameras: Query<(&Camera, &GlobalTransform), With<MatrixSoundCamera>>,
+    listeners: Query<(&SpatialListener, &GlobalTransform)>,
+    time: Res<Time>,
+) {
+    let cues: Vec<(f32, f32)> = spawned
+        .read()
+        .map(|letter| {
+            let pan = cameras
+                .get_single()
+                .ok()
+                .and_then(|(camera, transform)| camera.world_to_ndc(transform, letter.position))
+                .map_or(0.0, |ndc| ndc.x.clamp(-1.0, 1.0));
+            let log_scale = 10.0_f32.powf(letter.position.z / 10.0);
+            (pan, sound.volume * log_scale)
+        })
+        .collect();
+    if cues.is_empty() || sound.samples.is_empty() {
+        return;
+    }
+    let Some((listener, transform)) = listeners.iter().next() else {
+        return;
+    };
+
+    let volumes: Vec<f32> = cues.iter().map(|&(_, volume)| volume).collect();
+    let now = time.elapsed_seconds();
+    for index in sound.limiter.allocate(now, &volumes) {
+        let (pan, volume) = cues[index];
+        let Some((source, speed)) = with_rng(|rng| {
+            let source = sound.samples.choose(rng).cloned()?;
+            Some((source, rng.gen_range(sound.pitch.clone())))
+        }) else {
+            continue;
+        };
+        let ear = listener
+            .left_ear_offset
+            .lerp(listener.right_ear_offset, (pan + 1.0) / 2.0);
+        let position = transform.transform_point(ear);
+        commands.spawn((
+            AudioBundle {
+                source,
+                settings: PlaybackSettings::DESPAWN
+                    .with_volume(Volume::new(volume))
+                    .with_speed(speed)
+                    .with_spatial(true),
+            },
+            SpatialBundle::from_transform(Transform::from_translation(position)),
+        ));
+    }
+}
+
+impl Plugin for MatrixSoundPlugin {
+    fn build(&self, app: &mut App) {
+        let asset_server = app.world().get_resource::<AssetServer>().unwrap();
+        let samples = self
+            .samples
+            .iter()
+            .map(|path| asset_server.load(path.clone()))
+            .collect();
+
+        app.insert_resource(MatrixSound::new(samples))
+            .register_type::<MatrixSoundCamera>()
+            .add_systems(PostStartup, setup_listener)
+            .add_systems(Update, play_cues);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serves_loudest_cues_first() {
+        let mut limiter = VoiceLimiter::new(2, 1.0, 0.0);
+        assert_eq!(limiter.allocate(0.0, &[0.1, 0.5, 0.3]), [1, 2]);
+        assert_eq!(limiter.active(0.0), 2);
+        assert_eq!(limiter.active(1.0), 0);
+    }
+
+    #[test]
+    fn frees_voices_when_they_end() {
+        let mut limiter = VoiceLimiter::new(2, 1.0, 0.0);
+        limiter.allocate(0.0, &[1.0]);
+        assert_eq!(limiter.allocate(0.5, &[1.0, 1.0]), [0]);
+        assert!(limiter.allocate(0.9, &[1.0]).is_empty());
+        assert_eq!(limiter.allocate(1.0, &[1.0, 1.0]), [0]);
+        assert_eq!(limiter.active(1.0), 2);
+    }
+
+    #[test]
+    fn keeps_cues_apart() {
+        let mut limiter = VoiceLimiter::new(8, 0.1, 0.05);
+        assert_eq!(limiter.allocate(0.0, &[1.0]), [0]);
+        assert!(limiter.allocate(0.02, &[1.0]).is_empty());
+        assert_eq!(limiter.allocate(0.05, &[1.0]), [0]);
+        // Frames without cues don't hold the next one back.
+        assert!(limiter.allocate(0.2, &[]).is_empty());
+        assert_eq!(limiter.allocate(0.21, &[1.0]), [0]);
+    }
+
+    #[test]
+    fn without_voices_nothing_plays() {
+        let mut limiter = VoiceLimiter::new(0, 1.0, 0.0);
+        assert!(limiter.allocate(0.0, &[1.0]).is_empty());
+        assert_eq!(limiter.active(0.0), 0);
+    }
+}
@@ -0,0 +1,96 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    matrix_field::MatrixField,
+    matrix_strip::{MatrixStrip, StripState},
+};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FieldState {
+    /// Matches the field's `Name`. Unnamed states are matched by order.
+    pub name: Option<String>,
+    pub strips: Vec<StripState>,
+}
+
+/// Every strip and letter of the running rain, down to timers and tween
+/// progress. Unlike a [`crate::matrix_preset::MatrixPreset`] it holds no
+/// settings, only what the rain looks like at one instant.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct MatrixState {
+    pub fields: Vec<FieldState>,
+}
+
+impl MatrixState {
+    pub fn to_ron(&self) -> Result<String, String> {
+        ron::to_string(self).map_err(|e| e.to_string())
+    }
+
+    pub fn from_ron(text: &str) -> Result<Self, String> {
+        ron::from_str(text).map_err(|e| e.to_string())
+    }
+}
+
+/// Captures the state of every field in the world.
+pub fn save_state(world: &mut World) -> MatrixState {
+    let mut fields =
+        world.query_filtered::<(Option<&Name>, Option<&Children>), With<MatrixField>>();
+    let fields: Vec<_> = fields
+        .iter(world)
+        .map(|(name, children)| {
+            let children = children.map_or_else(Vec::new, |c| c.to_vec());
+            (name.map(|n| n.to_string()), children)
+        })
+        .collect();
+
+    MatrixState {
+        fields: fields
+            .into_iter()
+            .map(|(name, children)| FieldState {
+                name,
+                strips: children
+                    .into_iter()
+                    .filter_map(|child| StripState::capture(world, child))
+                    .collect(),
+            })
+            .collect(),
+    }
+}
+
+/// Replaces the strips of every field in the world with the ones in
+/// `state`. Fields without a matching state keep their strips.
+pub fn restore_state(world: &mut World, state: &MatrixState) {
+    let mut fields = world.query_filtered::<(Entity, Option<&Name>), With<MatrixField>>();
+    let fields: Vec<_> = fields
+        .iter(world)
+        .map(|(entity, name)| (entity, name.map(|n| n.to_string())))
+        .collect();
+
+    let mut unnamed = state.fields.iter().filter(|f| f.name.is_none());
+    for (entity, name) in fields {
+        let field_state = match name {
+            Some(name) => state
+                .fields
+                .iter()
+                .find(|f| f.name.as_deref() == Some(name.as_str())),
+            None => unnamed.next(),
+        };
+        let Some(field_state) = field_state else {
+            continue;
+        };
+
+        let strips: Vec<Entity> = world
+            .get::<Children>(entity)
+            .map_or(&[][..], |c| &**c)
+            .iter()
+            .copied()
+            .filter(|&child| world.get::<MatrixStrip>(child).is_some())
+            .collect();
+        for strip in strips {
+            world.entity_mut(strip).despawn_recursive();
+        }
+        for strip in &field_state.strips {
+            strip.spawn(world, entity);
+        }
+    }
+}
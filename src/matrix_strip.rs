@@ -1,24 +1,278 @@
 use std::time::Duration;
 
-use super::matrix_letter::*;
-use bevy::prelude::*;
+use super::{
+    matrix_field::MatrixField,
+    matrix_glyphs::MatrixGlyphs,
+    matrix_letter::*,
+    matrix_pointer::MatrixPointer,
+    utils::{timed, with_rng, with_sub_rng},
+};
+use bevy::{
+    ecs::{
+        entity::{EntityMapper, MapEntities},
+        reflect::ReflectMapEntities,
+    },
+    prelude::*,
+    render::view::RenderLayers,
+};
 use bevy_tweening::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Component, Default)]
+/// Colors of a strip: letters are spawned in `head` and fade to `body` once
+/// the next letter appears below them.
+///
+/// The emissions scale the colors in linear space. With the camera in HDR,
+/// only what ends up above the bloom threshold glows, so the head should
+/// be well above 1.0 and the body below.
+#[derive(Clone, Copy, Reflect, Serialize, Deserialize, Debug, PartialEq)]
+#[reflect(Default)]
+pub struct MatrixPalette {
+    pub head: Color,
+    pub body: Color,
+    #[serde(default = "default_head_emission")]
+    pub head_emission: f32,
+    #[serde(default = "default_body_emission")]
+    pub body_emission: f32,
+}
+
+fn default_head_emission() -> f32 {
+    4.0
+}
+
+fn default_body_emission() -> f32 {
+    0.6
+}
+
+impl Default for MatrixPalette {
+    fn default() -> Self {
+        Self {
+            head: Color::WHITE,
+            body: Color::srgb(0.1, 0.8, 0.3),
+            head_emission: default_head_emission(),
+            body_emission: default_body_emission(),
+        }
+    }
+}
+
+/// How often a strip's letters flip their glyphs, relative to
+/// [`MatrixLetterSettings::flip_interval`].
+///
+/// The head flips at `head`. Body letters start at `body` and slow down
+/// with age, freezing at `freeze_age` seconds.
+#[derive(Clone, Copy, Reflect, Serialize, Deserialize, Debug, PartialEq)]
+#[reflect(Default)]
+pub struct FlipCurve {
+    pub head: f32,
+    pub body: f32,
+    pub freeze_age: f32,
+    /// Shape of the slowdown, 1.0 is linear and higher values settle
+    /// sooner.
+    pub falloff: f32,
+}
+
+impl FlipCurve {
+    /// Scrambling head, shimmering body and a frozen tail.
+    pub const FILM: Self = Self {
+        head: 20.0,
+        body: 1.0,
+        freeze_age: 2.0,
+        falloff: 2.0,
+    };
+
+    /// Slow flips everywhere, nothing freezes.
+    pub const AMBIENT: Self = Self {
+        head: 3.0,
+        body: 0.3,
+        freeze_age: 30.0,
+        falloff: 1.0,
+    };
+
+    /// Relative flip rate of a letter `age` seconds old.
+    pub fn rate(&self, head: bool, age: f32) -> f32 {
+        if head {
+            return self.head;
+        }
+        let left = (1.0 - age / self.freeze_age.max(f32::EPSILON)).max(0.0);
+        self.body * left.powf(self.falloff)
+    }
+}
+
+impl Default for FlipCurve {
+    fn default() -> Self {
+        Self::FILM
+    }
+}
+
+/// Maps the depth of a strip to a size or brightness factor.
+#[derive(Clone, Copy, Reflect, Serialize, Deserialize, Debug, PartialEq)]
+#[reflect(Default)]
+pub enum DepthCurve {
+    /// `10^(z / falloff)`: 1.0 at depth 0, ten times as much `falloff`
+    /// units closer.
+    Exponential { falloff: f32 },
+    /// The same factor at every depth.
+    Constant(f32),
+}
+
+impl DepthCurve {
+    pub fn at(&self, z: f32) -> f32 {
+        match *self {
+            Self::Exponential { falloff } => {
+                // A falloff of 0 would make every depth but 0 infinite.
+                let falloff = falloff.abs().max(1e-3).copysign(falloff);
+                10.0_f32.powf(z / falloff)
+            }
+            Self::Constant(value) => value,
+        }
+    }
+}
+
+impl Default for DepthCurve {
+    fn default() -> Self {
+        Self::Exponential { falloff: 10.0 }
+    }
+}
+
+fn one() -> f32 {
+    1.0
+}
+
+/// Settings shared by all strips.
+#[derive(Resource, Reflect, Clone)]
+#[reflect(Resource)]
+pub struct MatrixStripSettings {
+    /// Drift velocity of a strip at depth 0, in world units per second.
+    pub drift: Vec2,
+    /// Time for a letter to fade from the head to the body color.
+    pub fade_time: f32,
+    /// Height of a letter at depth 0, in world units.
+    pub cell_size: f32,
+    /// Size of letters, and speed of strips, by depth.
+    pub size_curve: DepthCurve,
+    /// Brightness of letters by depth.
+    pub brightness_curve: DepthCurve,
+}
+
+impl Default for MatrixStripSettings {
+    fn default() -> Self {
+        Self {
+            drift: Vec2::new(-1.0, 0.3),
+            fade_time: 0.2,
+            cell_size: 1.0,
+            size_curve: DepthCurve::default(),
+            brightness_curve: DepthCurve::default(),
+        }
+    }
+}
+
+#[derive(Component, Reflect, Default)]
+#[reflect(Component, MapEntities)]
 pub struct MatrixStrip {
     num_spawned: u32,
     max_length: u32,
     log_scale: f32,
+    brightness: f32,
     lifetime: f32,
+    palette: MatrixPalette,
+    flip: FlipCurve,
+    /// Index in [`crate::matrix_glyphs::MatrixGlyphs`], or `None` to mix
+    /// the sets.
+    glyph_set: Option<usize>,
     last_spawn: Option<Entity>,
+    /// Hidden in the [`MatrixStripPool`], waiting to be reused.
+    pooled: bool,
+    /// State of the generator new letters draw their glyph and rotation
+    /// from, so a restored strip continues with the same letters.
+    seed: u64,
+}
+
+impl MatrixStrip {
+    /// Relative flip rate of `letter`, a child of this strip, at `age`
+    /// seconds old.
+    pub(crate) fn flip_rate(&self, letter: Entity, spawning: bool, age: f32) -> f32 {
+        let head = spawning && self.last_spawn == Some(letter);
+        self.flip.rate(head, age)
+    }
+
+    pub(crate) fn glyph_set(&self) -> Option<usize> {
+        self.glyph_set
+    }
+
+    /// Whether the strip is hidden in the [`MatrixStripPool`].
+    pub(crate) fn is_pooled(&self) -> bool {
+        self.pooled
+    }
 }
 
-#[derive(Component, Default)]
+impl MapEntities for MatrixStrip {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        if let Some(last) = &mut self.last_spawn {
+            *last = entity_mapper.map_entity(*last);
+        }
+    }
+}
+
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
 pub struct SpawnTimer(Timer);
 
-#[derive(Component, Default)]
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
 pub struct Spawning;
 
+/// Progress of a letter's color fade. Scenes carry it in place of the
+/// letter's `Animator`, which can't be serialized, and the animator is
+/// rebuilt from the strip's palette on load.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct LetterFade(pub Duration);
+
+/// Finished strips kept hidden for reuse, see
+/// [`crate::matrix_letter::MatrixLetterPool`].
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct MatrixStripPool {
+    /// Most strips kept in the pool, finished strips beyond it are
+    /// despawned.
+    pub capacity: usize,
+    /// Strips created at startup.
+    pub warm_up: usize,
+    free: Vec<Entity>,
+}
+
+impl Default for MatrixStripPool {
+    fn default() -> Self {
+        Self {
+            capacity: 512,
+            warm_up: 128,
+            free: Vec::new(),
+        }
+    }
+}
+
+impl MatrixStripPool {
+    /// Spawns a strip, reusing a pooled one if there is any.
+    pub fn spawn(&mut self, commands: &mut Commands, strip: MatrixStripBundle) -> Entity {
+        // Pooled strips may have been despawned by the app.
+        while let Some(entity) = self.free.pop() {
+            if let Some(mut pooled) = commands.get_entity(entity) {
+                pooled.insert(strip);
+                return entity;
+            }
+        }
+        commands.spawn(strip).id()
+    }
+
+    /// Number of strips waiting to be reused.
+    pub fn len(&self) -> usize {
+        self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.free.is_empty()
+    }
+}
+
 #[derive(Bundle)]
 pub struct MatrixStripBundle {
     transform: SpatialBundle,
@@ -30,19 +284,31 @@ pub struct MatrixStripPlugin;
 
 impl MatrixStripBundle {
     pub fn new(pos: Vec3) -> Self {
-        let log_scale = (10.0_f32).powf(pos.z / 10.0);
+        Self::from_settings(pos, &MatrixStripSettings::default())
+    }
+
+    /// A strip at `pos`, sized and lit for its depth by `settings`.
+    pub fn from_settings(pos: Vec3, settings: &MatrixStripSettings) -> Self {
+        let log_scale = settings.size_curve.at(pos.z);
+        let cell = settings.cell_size * log_scale;
         Self {
             transform: SpatialBundle {
                 transform: Transform::from_translation(Vec3::new(pos.x, pos.y, pos.z))
-                    .with_scale(Vec3::new(log_scale, log_scale, 1.0)),
+                    .with_scale(Vec3::new(cell, cell, 1.0)),
                 ..Default::default()
             },
             strip: MatrixStrip {
                 num_spawned: 0,
                 max_length: 40,
                 log_scale,
+                brightness: settings.brightness_curve.at(pos.z),
                 lifetime: 0.0,
+                palette: MatrixPalette::default(),
+                flip: FlipCurve::default(),
+                glyph_set: None,
                 last_spawn: None,
+                pooled: false,
+                seed: with_rng(|rng| rng.next_u64()),
             },
             spawning: Spawning,
             timer: SpawnTimer(Timer::new(
@@ -58,7 +324,23 @@ impl MatrixStripBundle {
     }
 
     pub fn with_lifetime(mut self, lifetime: f32) -> Self {
-        self.strip.lifetime = lifetime / self.strip.log_scale;
+        // Strips of size 0 still die.
+        self.strip.lifetime = lifetime / self.strip.log_scale.max(1e-3);
+        self
+    }
+
+    pub fn with_palette(mut self, palette: MatrixPalette) -> Self {
+        self.strip.palette = palette;
+        self
+    }
+
+    pub fn with_flip(mut self, flip: FlipCurve) -> Self {
+        self.strip.flip = flip;
+        self
+    }
+
+    pub fn with_glyph_set(mut self, glyph_set: Option<usize>) -> Self {
+        self.strip.glyph_set = glyph_set;
         self
     }
 
@@ -71,35 +353,217 @@ impl MatrixStripBundle {
     }
 }
 
+/// Snapshot of a strip and its letters, part of a
+/// [`crate::matrix_state::MatrixState`].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct StripState {
+    pub translation: Vec3,
+    pub num_spawned: u32,
+    pub max_length: u32,
+    pub log_scale: f32,
+    #[serde(default = "one")]
+    pub brightness: f32,
+    /// Letter height of the strip, depth scale included, in the units of
+    /// its field.
+    #[serde(default = "one")]
+    pub cell_size: f32,
+    pub lifetime: f32,
+    pub palette: MatrixPalette,
+    #[serde(default)]
+    pub flip: FlipCurve,
+    #[serde(default)]
+    pub glyph_set: Option<usize>,
+    pub spawning: bool,
+    pub spawn_period: Duration,
+    pub spawn_elapsed: Duration,
+    pub layers: Option<Vec<usize>>,
+    pub letters: Vec<LetterState>,
+    /// Index in `letters` of the current head.
+    pub last_spawn: Option<usize>,
+    /// The strip's generator state, see [`MatrixStrip`].
+    #[serde(default)]
+    pub seed: u64,
+}
+
+impl StripState {
+    pub(crate) fn capture(world: &World, entity: Entity) -> Option<Self> {
+        let strip = world.get::<MatrixStrip>(entity)?;
+        let timer = &world.get::<SpawnTimer>(entity)?.0;
+        let children = world.get::<Children>(entity).map_or(&[][..], |c| &**c);
+        let letters: Vec<_> = children
+            .iter()
+            .filter_map(|&child| Some((child, LetterState::capture(world, child)?)))
+            .collect();
+        Some(Self {
+            translation: world.get::<Transform>(entity)?.translation,
+            num_spawned: strip.num_spawned,
+            max_length: strip.max_length,
+            log_scale: strip.log_scale,
+            brightness: strip.brightness,
+            cell_size: world.get::<Transform>(entity)?.scale.y,
+            lifetime: strip.lifetime,
+            palette: strip.palette,
+            flip: strip.flip,
+            glyph_set: strip.glyph_set,
+            spawning: world.get::<Spawning>(entity).is_some(),
+            spawn_period: timer.duration(),
+            spawn_elapsed: timer.elapsed(),
+            layers: world
+                .get::<RenderLayers>(entity)
+                .map(|layers| layers.iter().collect()),
+            last_spawn: strip
+                .last_spawn
+                .and_then(|last| letters.iter().position(|(child, _)| *child == last)),
+            letters: letters.into_iter().map(|(_, letter)| letter).collect(),
+            seed: strip.seed,
+        })
+    }
+
+    /// Spawns the strip and its letters as children of `parent`.
+    pub(crate) fn spawn(&self, world: &mut World, parent: Entity) -> Entity {
+        let fade_time = world.resource::<MatrixStripSettings>().fade_time;
+        let layers = self.layers.as_deref().map(RenderLayers::from_layers);
+        let letters: Vec<Entity> = self
+            .letters
+            .iter()
+            .map(|state| {
+                let letter = state.spawn(world);
+                if let Some(elapsed) = state.fade {
+                    let mut animator = Animator::new(fade_tween(self.palette, fade_time));
+                    animator.tweenable_mut().set_elapsed(elapsed);
+                    world.entity_mut(letter).insert(animator);
+                }
+                if let Some(layers) = &layers {
+                    world.entity_mut(letter).insert(layers.clone());
+                }
+                letter
+            })
+            .collect();
+
+        let mut timer = Timer::new(self.spawn_period, TimerMode::Repeating);
+        timer.set_elapsed(self.spawn_elapsed);
+        let mut strip = world.spawn((
+            SpatialBundle {
+                transform: Transform::from_translation(self.translation).with_scale(Vec3::new(
+                    self.cell_size,
+                    self.cell_size,
+                    1.0,
+                )),
+                ..Default::default()
+            },
+            MatrixStrip {
+                num_spawned: self.num_spawned,
+                max_length: self.max_length,
+                log_scale: self.log_scale,
+                brightness: self.brightness,
+                lifetime: self.lifetime,
+                palette: self.palette,
+                flip: self.flip,
+                glyph_set: self.glyph_set,
+                last_spawn: self.last_spawn.and_then(|i| letters.get(i).copied()),
+                pooled: false,
+                seed: self.seed,
+            },
+            SpawnTimer(timer),
+        ));
+        if self.spawning {
+            strip.insert(Spawning);
+        }
+        if let Some(layers) = layers {
+            strip.insert(layers);
+        }
+        strip.push_children(&letters).set_parent(parent);
+        strip.id()
+    }
+}
+
+/// Sent when a field starts a new strip.
+#[derive(Event, Clone, Debug)]
+pub struct StripSpawned {
+    pub entity: Entity,
+    /// World position of the strip's top.
+    pub position: Vec3,
+}
+
+/// Sent when a strip reaches its full length and stops adding letters.
+#[derive(Event, Clone, Debug)]
+pub struct StripFinishedSpawning {
+    pub entity: Entity,
+    /// World position of the strip's top.
+    pub position: Vec3,
+}
+
+/// Sent when the last letter of a strip is gone and the strip is removed.
+#[derive(Event, Clone, Debug)]
+pub struct StripDespawned {
+    pub entity: Entity,
+    /// World position of the strip's top.
+    pub position: Vec3,
+}
+
+fn fade_tween(palette: MatrixPalette, fade_time: f32) -> Tween<MatrixLetter> {
+    Tween::new(
+        EaseFunction::QuadraticOut,
+        //TweeningType::Once,
+        Duration::from_secs_f32(fade_time),
+        MatrixLetterLens {
+            start: palette.head,
+            end: palette.body,
+            start_emission: palette.head_emission,
+            end_emission: palette.body_emission,
+        },
+    )
+    .with_repeat_count(RepeatCount::Finite(1))
+}
+
 fn spawn(
     mut commands: Commands,
-    mut query: Query<(Entity, &mut MatrixStrip, &mut SpawnTimer), With<Spawning>>,
+    mut query: Query<
+        (
+            Entity,
+            &mut MatrixStrip,
+            &mut SpawnTimer,
+            Option<&RenderLayers>,
+        ),
+        With<Spawning>,
+    >,
+    letters: Query<&Parent, With<MatrixLetter>>,
+    mut pool: ResMut<MatrixLetterPool>,
+    (settings, letter_settings): (Res<MatrixStripSettings>, Res<MatrixLetterSettings>),
+    glyphs: Res<MatrixGlyphs>,
     time: Res<Time>,
 ) {
-    for (entity, mut strip, mut timer) in &mut query {
+    for (entity, mut strip, mut timer, layers) in &mut query {
         timer.0.tick(time.delta());
         if timer.0.just_finished() {
-            if let Some(last) = strip.last_spawn {
-                let tween = Tween::new(
-                    EaseFunction::QuadraticOut,
-                    //TweeningType::Once,
-                    Duration::from_secs_f32(0.2),
-                    MatrixLetterLens {
-                        start: Color::WHITE,
-                        end: Color::srgb(0.1, 0.8, 0.3),
-                    },
-                )
-                .with_repeat_count(RepeatCount::Finite(1));
+            // The last letter may have died and been reused by another strip.
+            let last = strip
+                .last_spawn
+                .filter(|&last| letters.get(last).is_ok_and(|p| p.get() == entity));
+            if let Some(last) = last {
+                let tween = fade_tween(strip.palette, settings.fade_time);
                 commands.entity(last).insert(Animator::new(tween));
             }
             let pos = Vec3::new(0.0, -(strip.num_spawned as f32), 0.0);
-            let letter = commands
-                .spawn(
-                    MatrixLetterBundle::new(pos)
-                        .with_brightness(strip.log_scale)
-                        .with_lifetime(strip.lifetime),
-                )
-                .id();
+            let glyph_set = strip.glyph_set;
+            let (glyph, rotation) = with_sub_rng(&mut strip.seed, || {
+                let glyph = glyphs.pick(glyph_set).map_or(' ', |(glyph, _)| glyph);
+                (glyph, letter_settings.glyph_rotation())
+            });
+            let letter = pool.spawn(
+                &mut commands,
+                MatrixLetterBundle::new(pos)
+                    .with_glyph(glyph, rotation)
+                    .with_brightness(strip.brightness)
+                    .with_color(strip.palette.head)
+                    .with_emission(strip.palette.head_emission)
+                    .with_lifetime(strip.lifetime),
+            );
+            // Render layers aren't inherited, the letters have to carry them.
+            match layers {
+                Some(layers) => commands.entity(letter).insert(layers.clone()),
+                None => commands.entity(letter).remove::<RenderLayers>(),
+            };
             strip.num_spawned += 1;
             commands.entity(entity).add_child(letter);
             strip.last_spawn = Some(letter);
@@ -107,37 +571,206 @@ fn spawn(
     }
 }
 
-fn stop_spawn(mut commands: Commands, query: Query<(Entity, &MatrixStrip), With<Spawning>>) {
-    for (entity, strip) in &query {
+/// Resumes the color fades of letters loaded from a scene.
+fn restore_letter_fade(
+    mut commands: Commands,
+    query: Query<(Entity, &LetterFade, Option<&Parent>)>,
+    strips: Query<&MatrixStrip>,
+    settings: Res<MatrixStripSettings>,
+) {
+    for (entity, fade, parent) in &query {
+        let mut letter = commands.entity(entity);
+        letter.remove::<LetterFade>();
+        let Some(strip) = parent.and_then(|parent| strips.get(parent.get()).ok()) else {
+            continue;
+        };
+        let mut animator = Animator::new(fade_tween(strip.palette, settings.fade_time));
+        animator.tweenable_mut().set_elapsed(fade.0);
+        letter.insert(animator);
+    }
+}
+
+fn stop_spawn(
+    mut commands: Commands,
+    query: Query<(Entity, &MatrixStrip, &GlobalTransform), With<Spawning>>,
+    mut finished: EventWriter<StripFinishedSpawning>,
+) {
+    for (entity, strip, transform) in &query {
         if strip.num_spawned >= strip.max_length {
             commands.entity(entity).remove::<Spawning>();
+            finished.send(StripFinishedSpawning {
+                entity,
+                position: transform.translation(),
+            });
         }
     }
 }
 
 fn strip_clean(
     mut commands: Commands,
-    query: Query<(Entity, &Children), (Without<Spawning>, With<MatrixStrip>)>,
+    mut query: Query<
+        (
+            Entity,
+            &mut MatrixStrip,
+            &mut Visibility,
+            Option<&Children>,
+            Option<&Parent>,
+            &GlobalTransform,
+        ),
+        Without<Spawning>,
+    >,
+    mut pool: ResMut<MatrixStripPool>,
+    mut despawned: EventWriter<StripDespawned>,
 ) {
-    for (entity, children) in &query {
-        if children.len() == 0 {
+    for (entity, mut strip, mut visibility, children, parent, transform) in &mut query {
+        // `Children` is removed along with the last child.
+        if strip.pooled || children.is_some_and(|children| !children.is_empty()) {
+            continue;
+        }
+        despawned.send(StripDespawned {
+            entity,
+            position: transform.translation(),
+        });
+        if let Some(parent) = parent {
+            commands.entity(parent.get()).remove_children(&[entity]);
+        }
+        if pool.free.len() < pool.capacity {
+            strip.pooled = true;
+            *visibility = Visibility::Hidden;
+            // Layers are assigned again when the strip is reused.
+            commands.entity(entity).remove::<RenderLayers>();
+            pool.free.push(entity);
+        } else {
             commands.entity(entity).despawn_recursive();
         }
     }
 }
 
-fn move_strip(mut query: Query<(&MatrixStrip, &mut Transform)>, time: Res<Time>) {
-    for (strip, mut transform) in &mut query {
-        transform.translation.x -= time.delta_seconds() * strip.log_scale;
-        transform.translation.y += 0.3 * time.delta_seconds() * strip.log_scale;
+fn warm_up_pool(mut commands: Commands, mut pool: ResMut<MatrixStripPool>) {
+    let count = pool
+        .warm_up
+        .min(pool.capacity)
+        .saturating_sub(pool.free.len());
+    for _ in 0..count {
+        let strip = commands
+            .spawn((
+                SpatialBundle::HIDDEN_IDENTITY,
+                MatrixStrip {
+                    pooled: true,
+                    ..Default::default()
+                },
+                SpawnTimer::default(),
+            ))
+            .id();
+        pool.free.push(strip);
+    }
+}
+
+fn move_strip(
+    mut query: Query<(
+        &MatrixStrip,
+        &mut Transform,
+        &GlobalTransform,
+        Option<&Parent>,
+    )>,
+    parents: Query<&GlobalTransform, Without<MatrixStrip>>,
+    fields: Query<&MatrixField>,
+    pointer: Option<Res<MatrixPointer>>,
+    settings: Res<MatrixStripSettings>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_seconds();
+    for (strip, mut transform, global, parent) in &mut query {
+        if strip.pooled {
+            continue;
+        }
+        let speed = parent
+            .and_then(|parent| fields.get(parent.get()).ok())
+            .map_or(1.0, |field| field.speed);
+        transform.translation += (settings.drift * speed * dt * strip.log_scale).extend(0.0);
+
+        let field = parent.map(|parent| parent.get());
+        let Some((pointer, position)) = pointer
+            .as_deref()
+            .and_then(|p| Some((p, p.position_in(field)?)))
+        else {
+            continue;
+        };
+        // Push along the shortest way out from the pointer to the strip.
+        let top = global.translation().truncate();
+        let bottom = global
+            .transform_point(Vec3::new(0.0, -(strip.num_spawned as f32), 0.0))
+            .truncate();
+        let closest = if top == bottom {
+            top
+        } else {
+            let t = (position - bottom).dot(top - bottom) / (top - bottom).length_squared();
+            bottom.lerp(top, t.clamp(0.0, 1.0))
+        };
+        let falloff = pointer.falloff(field, closest);
+        if falloff <= 0.0 {
+            continue;
+        }
+        let push = (closest - position).normalize_or_zero()
+            * pointer.strength
+            * falloff
+            * strip.log_scale
+            * dt;
+        let push = match parent.and_then(|parent| parents.get(parent.get()).ok()) {
+            Some(parent) => parent
+                .affine()
+                .inverse()
+                .transform_vector3(push.extend(0.0)),
+            None => push.extend(0.0),
+        };
+        transform.translation += push;
     }
 }
 
 impl Plugin for MatrixStripPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, spawn)
+        app.init_resource::<MatrixStripSettings>()
+            .init_resource::<MatrixStripPool>()
+            .add_event::<StripSpawned>()
+            .add_event::<StripFinishedSpawning>()
+            .add_event::<StripDespawned>()
+            .register_type::<MatrixStrip>()
+            .register_type::<FlipCurve>()
+            .register_type::<DepthCurve>()
+            .register_type::<SpawnTimer>()
+            .register_type::<Spawning>()
+            .register_type::<LetterFade>()
+            .register_type::<MatrixStripSettings>()
+            .register_type::<MatrixStripPool>()
+            .add_systems(Startup, warm_up_pool)
+            .add_systems(Update, timed("spawn_letters", spawn))
             .add_systems(Update, stop_spawn)
+            .add_systems(Update, restore_letter_fade)
             .add_systems(Update, move_strip)
             .add_systems(Update, strip_clean);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn depth_curves() {
+        let curve = DepthCurve::Exponential { falloff: 2.0 };
+        assert_eq!(curve.at(0.0), 1.0);
+        assert!((curve.at(2.0) - 10.0).abs() < 1e-4);
+        assert!((curve.at(-2.0) - 0.1).abs() < 1e-6);
+        assert_eq!(DepthCurve::Constant(0.5).at(-3.0), 0.5);
+    }
+
+    #[test]
+    fn zero_falloff_stays_finite() {
+        for falloff in [0.0, -0.0] {
+            let curve = DepthCurve::Exponential { falloff };
+            assert_eq!(curve.at(0.0), 1.0);
+            assert!(curve.at(-1e-3).is_finite());
+            assert!(curve.at(1e-3).is_finite());
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use bevy::{
+    prelude::*,
+    render::{
+        camera::{RenderTarget, ScalingMode},
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages},
+        view::RenderLayers,
+    },
+};
+
+use crate::matrix_field::{spawn_strips, MatrixField};
+
+/// Renders a [`MatrixField`] into an offscreen image instead of the window.
+///
+/// Put it on the field entity. The field's strips are moved onto `layer`
+/// and a [`MatrixTextureCamera`] is spawned below the field that renders
+/// that layer into [`MatrixTexture::image`], which can then be used on a
+/// mesh material or as a UI image. Changing `size` resizes the image.
+#[derive(Component, Reflect, Clone)]
+#[reflect(Component)]
+pub struct MatrixTexture {
+    pub size: UVec2,
+    /// Visible height of the field in world units.
+    pub view_height: f32,
+    /// Where the pointer is on the image, from (0, 0) at the top left to
+    /// (1, 1) at the bottom right. Only the app knows where the image is
+    /// shown, so it sets this, e.g. from a UI node or a mesh raycast.
+    /// `None` leaves the field undisturbed.
+    pub pointer: Option<Vec2>,
+    layer: usize,
+    image: Handle<Image>,
+}
+
+/// Marks the camera rendering a [`MatrixTexture`], e.g. to add bloom to it.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct MatrixTextureCamera;
+
+impl MatrixTexture {
+    pub fn new(images: &mut Assets<Image>, size: UVec2, layer: usize) -> Self {
+        Self {
+            size,
+            view_height: 16.0,
+            pointer: None,
+            layer,
+            image: images.add(texture_image(size)),
+        }
+    }
+
+    pub fn with_view_height(mut self, view_height: f32) -> Self {
+        self.view_height = view_height;
+        self
+    }
+
+    pub fn image(&self) -> Handle<Image> {
+        self.image.clone()
+    }
+
+    pub fn layer(&self) -> usize {
+        self.layer
+    }
+}
+
+pub struct MatrixTexturePlugin;
+
+fn texture_extent(size: UVec2) -> Extent3d {
+    Extent3d {
+        width: size.x.max(1),
+        height: size.y.max(1),
+        ..default()
+    }
+}
+
+fn texture_image(size: UVec2) -> Image {
+    let mut image = Image::new_fill(
+        texture_extent(size),
+        TextureDimension::D2,
+        &[0; 4],
+        TextureFormat::Bgra8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    image.texture_descriptor.usage =
+        TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST | TextureUsages::RENDER_ATTACHMENT;
+    image
+}
+
+fn setup_texture_camera(
+    mut commands: Commands,
+    query: Query<(Entity, &MatrixTexture), (Added<MatrixTexture>, With<MatrixField>)>,
+) {
+    for (entity, texture) in &query {
+        let layers = RenderLayers::layer(texture.layer);
+        let mut camera = Camera2dBundle {
+            transform: Transform::from_translation(Vec3::new(0.0, 0.0, 100.0)),
+            camera: Camera {
+                hdr: true,
+                target: RenderTarget::Image(texture.image()),
+                ..default()
+            },
+            ..default()
+        };
+        camera.projection.scaling_mode = ScalingMode::FixedVertical(texture.view_height);
+        let camera = commands
+            .spawn((camera, layers.clone(), MatrixTextureCamera))
+            .id();
+        commands.entity(entity).insert(layers).add_child(camera);
+    }
+}
+
+fn resize_texture(
+    mut images: ResMut<Assets<Image>>,
+    query: Query<(&MatrixTexture, &Children), Changed<MatrixTexture>>,
+    mut cameras: Query<&mut OrthographicProjection, With<MatrixTextureCamera>>,
+) {
+    for (texture, children) in &query {
+        if images
+            .get(&texture.image)
+            .is_some_and(|image| image.size() != texture.size)
+        {
+            images
+                .get_mut(&texture.image)
+                .unwrap()
+                .resize(texture_extent(texture.size));
+        }
+        let mut iter = cameras.iter_many_mut(children);
+        while let Some(mut projection) = iter.fetch_next() {
+            projection.scaling_mode = ScalingMode::FixedVertical(texture.view_height);
+        }
+    }
+}
+
+impl Plugin for MatrixTexturePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<MatrixTexture>()
+            .register_type::<MatrixTextureCamera>()
+            .add_systems(Update, setup_texture_camera.before(spawn_strips))
+            .add_systems(Update, resize_texture);
+    }
+}
@@ -1,41 +1,141 @@
-use bevy::render::render_graph::{Node, NodeRunError, RenderGraphContext, SlotInfo, SlotType};
-use bevy::render::render_resource::{
-    BindGroupDescriptor, BindGroupEntry, BindingResource, FilterMode, Operations,
-    RenderPassColorAttachment, RenderPassDescriptor, SamplerDescriptor,
-};
-use bevy::render::renderer::RenderContext;
-use bevy::render::RenderSet;
 use bevy::{
-    core_pipeline::fullscreen_vertex_shader::fullscreen_shader_vertex_state,
-    ecs::query::QueryItem,
+    core_pipeline::{
+        core_2d::graph::{Core2d, Node2d},
+        fullscreen_vertex_shader::fullscreen_shader_vertex_state,
+    },
+    ecs::{entity::EntityHashMap, query::QueryItem},
     prelude::*,
-    reflect::erased_serde::__private::serde::__private::de::TagContentOtherFieldVisitor,
     render::{
+        camera::{CameraMainTextureUsages, ExtractedCamera},
         extract_component::{ExtractComponent, ExtractComponentPlugin},
+        globals::{GlobalsBuffer, GlobalsUniform},
+        render_graph::{
+            NodeRunError, RenderGraph, RenderGraphApp, RenderGraphContext, RenderLabel, ViewNode,
+            ViewNodeRunner,
+        },
         render_resource::{
-            BindGroup, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry,
-            BindingType, CachedRenderPipelineId, ColorTargetState, ColorWrites, FragmentState,
-            MultisampleState, PipelineCache, PrimitiveState, RenderPipelineDescriptor,
-            SamplerBindingType, ShaderStages, SpecializedRenderPipeline,
-            SpecializedRenderPipelines, TextureFormat, TextureSampleType, TextureViewDimension,
-            TextureViewId,
+            binding_types::{sampler, texture_2d, uniform_buffer},
+            BindGroupEntries, BindGroupLayout, BindGroupLayoutEntries, CachedRenderPipelineId,
+            ColorTargetState, ColorWrites, Extent3d, FilterMode, FragmentState, MultisampleState,
+            Operations, PipelineCache, PrimitiveState, RenderPassColorAttachment,
+            RenderPassDescriptor, RenderPipelineDescriptor, Sampler, SamplerBindingType,
+            SamplerDescriptor, ShaderStages, SpecializedRenderPipeline, SpecializedRenderPipelines,
+            Texture, TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType,
+            TextureUsages, TextureView, TextureViewDescriptor, UniformBuffer,
         },
-        renderer::RenderDevice,
+        renderer::{RenderContext, RenderDevice, RenderQueue},
         texture::BevyDefault,
         view::{ExtractedView, ViewTarget},
-        RenderApp,
+        Render, RenderApp, RenderSet,
     },
 };
-use bevy_editor_pls::egui::mutex::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    matrix_glitch::{GlitchKind, MatrixGlitches},
+    matrix_grading::GradingLabel,
+};
 
-#[derive(Component, Clone)]
+/// Phosphor layout drawn over the image.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Reflect, Serialize, Deserialize)]
+pub enum CrtMask {
+    None,
+    /// Dots in staggered red, green and blue triads.
+    ShadowMask,
+    /// Continuous vertical red, green and blue stripes.
+    #[default]
+    ApertureGrille,
+}
+
+/// CRT screen simulation, placed on a camera. Runs after tonemapping and
+/// grading, and also draws the [`crate::matrix_glitch::MatrixGlitch`]
+/// bursts, which still show while the CRT is disabled.
+#[derive(Component, Reflect, Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[reflect(Component, Default)]
 pub struct MatrixPost {
     pub enabled: bool,
+    /// Positive bulges the screen like a CRT, negative pinches it.
+    pub curvature: f32,
+    /// Radius of the screen corners, as a fraction of the screen height.
+    pub corner_radius: f32,
+    pub mask: CrtMask,
+    pub mask_strength: f32,
+    /// Width of one red, green and blue triad, in pixels.
+    pub mask_size: f32,
+    /// Brightness added by the rolling interference bar, negative darkens.
+    pub bar_strength: f32,
+    /// Screen heights per second.
+    pub bar_speed: f32,
+    /// Height of the bar, as a fraction of the screen height.
+    pub bar_height: f32,
+    /// How much the brightness jitters from frame to frame.
+    pub flicker: f32,
 }
 
 impl Default for MatrixPost {
     fn default() -> Self {
-        Self { enabled: true }
+        Self {
+            enabled: true,
+            curvature: 0.12,
+            corner_radius: 0.05,
+            mask: CrtMask::default(),
+            mask_strength: 0.3,
+            mask_size: 3.0,
+            bar_strength: 0.06,
+            bar_speed: 0.08,
+            bar_height: 0.2,
+            flicker: 0.03,
+        }
+    }
+}
+
+impl MatrixPost {
+    pub fn with_curvature(mut self, curvature: f32) -> Self {
+        self.curvature = curvature;
+        self
+    }
+
+    pub fn with_mask(mut self, mask: CrtMask, strength: f32) -> Self {
+        self.mask = mask;
+        self.mask_strength = strength;
+        self
+    }
+
+    pub fn with_bar(mut self, strength: f32, speed: f32) -> Self {
+        self.bar_strength = strength;
+        self.bar_speed = speed;
+        self
+    }
+
+    pub fn with_flicker(mut self, flicker: f32) -> Self {
+        self.flicker = flicker;
+        self
+    }
+
+    /// The settings and current glitch levels as the shader reads them.
+    pub fn uniform(&self, glitches: Option<&MatrixGlitches>) -> [Vec4; 4] {
+        let level = |kind| glitches.map_or(0.0, |g| g.level(kind));
+        [
+            Vec4::new(
+                self.curvature,
+                self.corner_radius,
+                self.mask_strength,
+                self.mask_size.max(1.0),
+            ),
+            Vec4::new(
+                self.bar_strength,
+                self.bar_speed,
+                self.bar_height.max(f32::EPSILON),
+                self.flicker,
+            ),
+            Vec4::new(
+                level(GlitchKind::Tear),
+                level(GlitchKind::RgbSplit),
+                level(GlitchKind::FrozenBlocks),
+                level(GlitchKind::Invert),
+            ),
+            Vec4::new(glitches.map_or(0.0, |g| g.seed()), 0.0, 0.0, 0.0),
+        ]
     }
 }
 
@@ -44,65 +144,101 @@ impl ExtractComponent for MatrixPost {
     type QueryFilter = With<Camera>;
     type Out = MatrixPost;
 
+    /// Disabled posts are extracted too, glitches still run on them.
     fn extract_component(item: QueryItem<Self::QueryData>) -> Option<Self> {
         Some(item.clone())
     }
 }
 
-pub const MATRIX_POST_NODE: &str = "matrix_post_node";
+/// Frozen blocks show the last frame from before the glitch, copied out of
+/// the camera's main texture.
+fn allow_history_copy(mut cameras: Query<&mut CameraMainTextureUsages, Added<MatrixPost>>) {
+    for mut usages in &mut cameras {
+        usages.0 |= TextureUsages::COPY_SRC;
+    }
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+pub struct MatrixPostLabel;
 
 pub struct MatrixPostPlugin;
+
 impl Plugin for MatrixPostPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(ExtractComponentPlugin::<MatrixPost>::default());
+        app.add_plugins(ExtractComponentPlugin::<MatrixPost>::default())
+            .register_type::<MatrixPost>()
+            .add_systems(PostUpdate, allow_history_copy);
 
-        let render_app = match app.get_sub_app_mut(RenderApp) {
-            Some(render_app) => render_app,
-            None => return,
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
         };
-
         render_app
-            .init_resource::<MatrixPostPipeline>()
             .init_resource::<SpecializedRenderPipelines<MatrixPostPipeline>>()
-            .add_systems(prepare_matrix_post_pipelines.in_base_set(RenderSet::Prepare));
+            .init_resource::<MatrixPostHistories>()
+            .add_systems(
+                Render,
+                prepare_matrix_post_pipelines.in_set(RenderSet::Prepare),
+            )
+            .add_render_graph_node::<ViewNodeRunner<MatrixPostNode>>(Core2d, MatrixPostLabel)
+            .add_render_graph_edges(
+                Core2d,
+                (
+                    Node2d::Tonemapping,
+                    MatrixPostLabel,
+                    Node2d::EndMainPassPostProcessing,
+                ),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app.init_resource::<MatrixPostPipeline>();
+        // The screen goes over the graded image.
+        let mut graph = render_app.world_mut().resource_mut::<RenderGraph>();
+        if let Some(core_2d) = graph.get_sub_graph_mut(Core2d) {
+            if core_2d.get_node_state(GradingLabel).is_ok() {
+                core_2d.add_node_edge(GradingLabel, MatrixPostLabel);
+            }
+        }
     }
 }
 
 #[derive(Resource)]
 pub struct MatrixPostPipeline {
     texture_bind_group: BindGroupLayout,
+    sampler: Sampler,
     frag_shader: Handle<Shader>,
 }
 
 impl FromWorld for MatrixPostPipeline {
     fn from_world(render_world: &mut World) -> Self {
-        let asset_server = render_world.get_resource::<AssetServer>().unwrap();
-        let frag_shader: Handle<Shader> = asset_server.load("shaders/post.wgsl");
-        let texture_bind_group = render_world
-            .resource::<RenderDevice>()
-            .create_bind_group_layout(&BindGroupLayoutDescriptor {
-                label: Some("matrix_post_bind_group_layout"),
-                entries: &[
-                    BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: ShaderStages::FRAGMENT,
-                        ty: BindingType::Texture {
-                            sample_type: TextureSampleType::Float { filterable: true },
-                            view_dimension: TextureViewDimension::D2,
-                            multisampled: false,
-                        },
-                        count: None,
-                    },
-                    BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: ShaderStages::FRAGMENT,
-                        ty: BindingType::Sampler(SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                ],
-            });
+        let frag_shader = render_world
+            .resource::<AssetServer>()
+            .load("shaders/post.wgsl");
+        let render_device = render_world.resource::<RenderDevice>();
+        let texture_bind_group = render_device.create_bind_group_layout(
+            "matrix_post_bind_group_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::FRAGMENT,
+                (
+                    texture_2d(TextureSampleType::Float { filterable: true }),
+                    sampler(SamplerBindingType::Filtering),
+                    uniform_buffer::<[Vec4; 4]>(false),
+                    uniform_buffer::<GlobalsUniform>(false),
+                    texture_2d(TextureSampleType::Float { filterable: true }),
+                ),
+            ),
+        );
+        let sampler = render_device.create_sampler(&SamplerDescriptor {
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..default()
+        });
         Self {
             texture_bind_group,
+            sampler,
             frag_shader,
         }
     }
@@ -111,27 +247,55 @@ impl FromWorld for MatrixPostPipeline {
 #[derive(Component)]
 pub struct CameraMatrixPostPipeline {
     pub pipeline_id: CachedRenderPipelineId,
+    settings: UniformBuffer<[Vec4; 4]>,
+    /// The last frame without frozen blocks, when glitches are on.
+    history: Option<TextureView>,
+    /// Set on frames that copy the main texture into the history.
+    copy_history: Option<Texture>,
+    /// Whether the pass runs, or the view only updates its history.
+    draw: bool,
 }
 
+struct PostHistory {
+    texture: Texture,
+    view: TextureView,
+    /// Holds a frame from before the running frozen blocks burst, or from
+    /// the last frame when bursts start at random.
+    filled: bool,
+}
+
+/// History texture of each post view, kept while glitches are on.
+#[derive(Resource, Default)]
+pub struct MatrixPostHistories(EntityHashMap<PostHistory>);
+
 #[derive(PartialEq, Eq, Hash, Clone, Copy)]
 pub struct MatrixPostPipelineKey {
     texture_format: TextureFormat,
+    mask: CrtMask,
+    /// Without it the pass only draws glitches.
+    crt: bool,
 }
 
 impl SpecializedRenderPipeline for MatrixPostPipeline {
     type Key = MatrixPostPipelineKey;
 
     fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        let mut shader_defs = match key.mask {
+            _ if !key.crt => vec![],
+            CrtMask::None => vec![],
+            CrtMask::ShadowMask => vec!["CRT_MASK".into(), "SHADOW_MASK".into()],
+            CrtMask::ApertureGrille => vec!["CRT_MASK".into()],
+        };
+        if key.crt {
+            shader_defs.push("CRT".into());
+        }
         RenderPipelineDescriptor {
             label: Some("matrix_post".into()),
-            layout: Some(vec![self.texture_bind_group.clone()]),
+            layout: vec![self.texture_bind_group.clone()],
             vertex: fullscreen_shader_vertex_state(),
-            primitive: PrimitiveState::default(),
-            depth_stencil: None,
-            multisample: MultisampleState::default(),
             fragment: Some(FragmentState {
                 shader: self.frag_shader.clone(),
-                shader_defs: vec![],
+                shader_defs,
                 entry_point: "fragment".into(),
                 targets: vec![Some(ColorTargetState {
                     format: key.texture_format,
@@ -139,149 +303,174 @@ impl SpecializedRenderPipeline for MatrixPostPipeline {
                     write_mask: ColorWrites::ALL,
                 })],
             }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            push_constant_ranges: Vec::new(),
         }
     }
 }
 
+fn view_format(view: &ExtractedView) -> TextureFormat {
+    if view.hdr {
+        ViewTarget::TEXTURE_FORMAT_HDR
+    } else {
+        TextureFormat::bevy_default()
+    }
+}
+
 pub fn prepare_matrix_post_pipelines(
     mut commands: Commands,
-    mut pipeline_cache: ResMut<PipelineCache>,
+    pipeline_cache: Res<PipelineCache>,
     mut pipelines: ResMut<SpecializedRenderPipelines<MatrixPostPipeline>>,
     matrix_post_pipeline: Res<MatrixPostPipeline>,
-    views: Query<(Entity, &ExtractedView, &MatrixPost)>,
+    (render_device, render_queue): (Res<RenderDevice>, Res<RenderQueue>),
+    (glitches, mut histories): (Option<Res<MatrixGlitches>>, ResMut<MatrixPostHistories>),
+    views: Query<(Entity, &ExtractedView, &ExtractedCamera, &MatrixPost)>,
 ) {
-    for (entity, view, post) in &views {
-        if !post.enabled {
+    let glitches = glitches.as_deref();
+    if glitches.is_some() {
+        histories.0.retain(|entity, _| views.contains(*entity));
+    } else {
+        histories.0.clear();
+    }
+    let random = glitches.is_some_and(|g| g.random_interval.is_some());
+    let frozen = glitches.is_some_and(|g| g.level(GlitchKind::FrozenBlocks) > 0.0);
+    for (entity, view, camera, post) in &views {
+        let format = view_format(view);
+        let history = glitches.and(camera.physical_target_size).map(|size| {
+            let history = histories
+                .0
+                .entry(entity)
+                .or_insert_with(|| new_history(&render_device, size, format));
+            if history.texture.width() != size.x
+                || history.texture.height() != size.y
+                || history.texture.format() != format
+            {
+                *history = new_history(&render_device, size, format);
+            }
+            // While frozen the history keeps the frame from before the
+            // burst. Bursts sent as events take the frame they start on.
+            let copy = if frozen { !history.filled } else { random };
+            history.filled = frozen || random;
+            (history.view.clone(), copy.then(|| history.texture.clone()))
+        });
+        let (history, copy_history) = history.unzip();
+        let copy_history = copy_history.flatten();
+        let draw = post.enabled || glitches.is_some_and(MatrixGlitches::is_active);
+        if !draw && copy_history.is_none() {
             continue;
         }
 
         let pipeline_id = pipelines.specialize(
-            &mut pipeline_cache,
+            &pipeline_cache,
             &matrix_post_pipeline,
             MatrixPostPipelineKey {
-                texture_format: if view.hdr {
-                    ViewTarget::TEXTURE_FORMAT_HDR
-                } else {
-                    TextureFormat::bevy_default()
-                },
+                texture_format: format,
+                mask: post.mask,
+                crt: post.enabled,
             },
         );
-
-        commands
-            .entity(entity)
-            .insert(CameraMatrixPostPipeline { pipeline_id });
+        let mut settings = UniformBuffer::from(post.uniform(glitches));
+        settings.write_buffer(&render_device, &render_queue);
+        commands.entity(entity).insert(CameraMatrixPostPipeline {
+            pipeline_id,
+            settings,
+            history,
+            copy_history,
+            draw,
+        });
     }
 }
 
-pub struct MatrixPostNode {
-    query: QueryState<(
-        &'static ViewTarget,
-        &'static CameraMatrixPostPipeline,
-        &'static MatrixPost,
-    )>,
-    cached_texture_bind_group: Mutex<Option<(TextureViewId, BindGroup)>>,
-}
-
-impl MatrixPostNode {
-    pub const IN_VIEW: &'static str = "view";
-
-    pub fn new(world: &mut World) -> Self {
-        Self {
-            query: QueryState::new(world),
-            cached_texture_bind_group: Mutex::new(None),
-        }
+fn new_history(render_device: &RenderDevice, size: UVec2, format: TextureFormat) -> PostHistory {
+    let texture = render_device.create_texture(&TextureDescriptor {
+        label: Some("matrix_post_history"),
+        size: Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format,
+        usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&TextureViewDescriptor::default());
+    PostHistory {
+        texture,
+        view,
+        filled: false,
     }
 }
 
-impl Node for MatrixPostNode {
-    fn input(&self) -> Vec<SlotInfo> {
-        vec![SlotInfo::new(Self::IN_VIEW, SlotType::Entity)]
-    }
+#[derive(Default)]
+pub struct MatrixPostNode;
 
-    fn update(&mut self, world: &mut World) {
-        self.query.update_archetypes(world)
-    }
+impl ViewNode for MatrixPostNode {
+    type ViewQuery = (&'static ViewTarget, &'static CameraMatrixPostPipeline);
 
     fn run(
         &self,
-        graph: &mut RenderGraphContext,
+        _graph: &mut RenderGraphContext,
         render_context: &mut RenderContext,
+        (target, pipeline): QueryItem<Self::ViewQuery>,
         world: &World,
     ) -> Result<(), NodeRunError> {
-        let view_entity = graph.get_input_entity(Self::IN_VIEW)?;
-        let pipeline_cache = world.resource::<PipelineCache>();
         let matrix_post_pipeline = world.resource::<MatrixPostPipeline>();
-
-        let (target, pipeline, matrix_post) = match self.query.get_manual(world, view_entity) {
-            Ok(result) => result,
-            Err(_) => return Ok(()),
+        let Some(render_pipeline) = world
+            .resource::<PipelineCache>()
+            .get_render_pipeline(pipeline.pipeline_id)
+        else {
+            return Ok(());
+        };
+        let (Some(settings), Some(globals)) = (
+            pipeline.settings.binding(),
+            world.resource::<GlobalsBuffer>().buffer.binding(),
+        ) else {
+            return Ok(());
         };
 
-        if !matrix_post.enabled {
+        if let Some(history) = &pipeline.copy_history {
+            render_context.command_encoder().copy_texture_to_texture(
+                target.main_texture().as_image_copy(),
+                history.as_image_copy(),
+                history.size(),
+            );
+        }
+        if !pipeline.draw {
             return Ok(());
         }
 
-        let pipeline = pipeline_cache
-            .get_render_pipeline(pipeline.pipeline_id)
-            .unwrap();
-
         let post_process = target.post_process_write();
-        let source = post_process.source;
-        let destination = post_process.destination;
-        let mut cached_bind_group = self.cached_texture_bind_group.lock();
-        let bind_group = match &mut *cached_bind_group {
-            Some((id, bind_group)) if source.id() == *id => bind_group,
-            cached_bind_group => {
-                let sampler = render_context
-                    .render_device
-                    .create_sampler(&SamplerDescriptor {
-                        mipmap_filter: FilterMode::Linear,
-                        mag_filter: FilterMode::Linear,
-                        min_filter: FilterMode::Linear,
-                        ..Default::default()
-                    });
-
-                let bind_group =
-                    render_context
-                        .render_device
-                        .create_bind_group(&BindGroupDescriptor {
-                            label: None,
-                            layout: &matrix_post_pipeline.texture_bind_group,
-                            entries: &[
-                                BindGroupEntry {
-                                    binding: 0,
-                                    resource: BindingResource::TextureView(source),
-                                },
-                                BindGroupEntry {
-                                    binding: 1,
-                                    resource: BindingResource::Sampler(&sampler),
-                                },
-                            ],
-                        });
-
-                let (_, bind_group) = cached_bind_group.insert((source.id(), bind_group));
-                bind_group
-            }
-        };
+        let bind_group = render_context.render_device().create_bind_group(
+            "matrix_post_bind_group",
+            &matrix_post_pipeline.texture_bind_group,
+            &BindGroupEntries::sequential((
+                post_process.source,
+                &matrix_post_pipeline.sampler,
+                settings,
+                globals,
+                pipeline.history.as_ref().unwrap_or(post_process.source),
+            )),
+        );
 
-        let pass_descriptor = RenderPassDescriptor {
+        let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
             label: Some("matrix_post_pass"),
             color_attachments: &[Some(RenderPassColorAttachment {
-                view: destination,
+                view: post_process.destination,
                 resolve_target: None,
                 ops: Operations::default(),
             })],
             depth_stencil_attachment: None,
-        };
-
-        let mut render_pass = render_context
-            .command_encoder
-            .begin_render_pass(&pass_descriptor);
-
-        render_pass.set_pipeline(pipeline);
-        render_pass.set_bind_group(0, bind_group, &[]);
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_render_pipeline(render_pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
         render_pass.draw(0..3, 0..1);
-
         Ok(())
     }
 }
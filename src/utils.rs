@@ -1,8 +1,102 @@
-use rand::{thread_rng, Rng};
+use bevy::ecs::system::{CombinatorSystem, Combine, System};
+use bevy::prelude::*;
+use bevy::utils::{Duration, Instant};
+use rand::{rngs::StdRng, thread_rng, RngCore, SeedableRng};
+use std::cell::RefCell;
 use std::f32::consts::E;
+use std::sync::{Arc, Mutex};
+
+thread_local! {
+    static SEEDED: RefCell<Option<StdRng>> = const { RefCell::new(None) };
+}
+
+/// Makes the random numbers drawn on this thread reproducible. Systems only
+/// see them when the schedules run single-threaded.
+pub(crate) fn seed_rng(seed: u64) {
+    SEEDED.with_borrow_mut(|rng| *rng = Some(StdRng::seed_from_u64(seed)));
+}
+
+/// Runs `f` with the seeded generator, or the thread's random one.
+pub(crate) fn with_rng<T>(f: impl FnOnce(&mut dyn RngCore) -> T) -> T {
+    SEEDED.with_borrow_mut(|seeded| match seeded {
+        Some(rng) => f(rng),
+        None => f(&mut thread_rng()),
+    })
+}
+
+/// Runs `f` with a generator seeded from `seed`, so whatever `f` draws
+/// through [`with_rng`] depends on nothing else, then moves `seed` on.
+pub(crate) fn with_sub_rng<T>(seed: &mut u64, f: impl FnOnce() -> T) -> T {
+    let outer = SEEDED.with_borrow_mut(|rng| rng.replace(StdRng::seed_from_u64(*seed)));
+    let out = f();
+    SEEDED.with_borrow_mut(|rng| {
+        *seed = rng.as_mut().map_or(*seed, |rng| rng.next_u64());
+        *rng = outer;
+    });
+    out
+}
 
 pub fn exponential_event(t_average: f32, dt: f32) -> bool {
-    let mut rng = thread_rng();
     let probability = 1. - E.powf(-dt / t_average);
-    rng.gen::<f32>() < probability
+    with_rng(|rng| rand::Rng::gen::<f32>(rng) < probability)
+}
+
+/// Time spent in every [`timed`] system since the last [`Self::take`].
+/// Systems are only timed while this resource exists.
+#[derive(Resource, Clone, Default)]
+pub(crate) struct SystemTimings(Arc<Mutex<Vec<(&'static str, Duration)>>>);
+
+impl SystemTimings {
+    pub(crate) fn take(&self) -> Vec<(&'static str, Duration)> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+
+    fn add(&self, name: &'static str, elapsed: Duration) {
+        let mut timings = self.0.lock().unwrap();
+        match timings.iter_mut().find(|(timed, _)| *timed == name) {
+            Some((_, total)) => *total += elapsed,
+            None => timings.push((name, elapsed)),
+        }
+    }
+}
+
+/// Runs the second system, timed if the first one returns where to record
+/// it.
+pub(crate) struct Timed;
+
+impl<A, B> Combine<A, B> for Timed
+where
+    A: System<In = (), Out = Option<(&'static str, SystemTimings)>>,
+    B: System<In = ()>,
+{
+    type In = ();
+    type Out = B::Out;
+
+    fn combine(
+        (): (),
+        probe: impl FnOnce(()) -> A::Out,
+        system: impl FnOnce(()) -> B::Out,
+    ) -> B::Out {
+        let Some((name, timings)) = probe(()) else {
+            return system(());
+        };
+        let start = Instant::now();
+        let out = system(());
+        timings.add(name, start.elapsed());
+        out
+    }
+}
+
+/// Wraps `system` so its run time is recorded under `name` in the
+/// [`SystemTimings`], if there are any.
+pub(crate) fn timed<M, S: IntoSystem<(), (), M>>(
+    name: &'static str,
+    system: S,
+) -> impl System<In = (), Out = ()> {
+    let probe = IntoSystem::into_system(move |timings: Option<Res<SystemTimings>>| {
+        timings.map(|timings| (name, timings.clone()))
+    });
+    let system = IntoSystem::into_system(system);
+    let system_name = system.name();
+    CombinatorSystem::<Timed, _, _>::new(probe, system, system_name)
 }
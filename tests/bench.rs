@@ -0,0 +1,45 @@
+use std::process::Command;
+
+use bevy_matrix::matrix_bench::{run_bench, BenchSettings, TIMED_SYSTEMS};
+
+#[test]
+fn seeded_runs_match() {
+    let settings = BenchSettings {
+        densities: vec![50.0],
+        seconds: 2.0,
+        ..Default::default()
+    };
+    let first = run_bench(&settings);
+    let second = run_bench(&settings);
+    let (a, b) = (&first.runs[0], &second.runs[0]);
+    assert!(a.strips.max > 0.0 && a.letters.max > 0.0);
+    assert_eq!(a.strips, b.strips);
+    assert_eq!(a.letters, b.letters);
+    assert_eq!(a.entities, b.entities);
+
+    // Every system is timed under its own name.
+    let names: Vec<&str> = a.systems.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(names, TIMED_SYSTEMS);
+    for system in &a.systems {
+        assert!(system.time.max > 0.0, "{} never timed", system.name);
+    }
+}
+
+#[test]
+fn rejects_bad_arguments() {
+    for args in [
+        ["--fps", "0"],
+        ["--fps", "-30"],
+        ["--seconds", "-1"],
+        ["--seconds", "NaN"],
+    ] {
+        let output = Command::new(env!("CARGO_BIN_EXE_bench"))
+            .args(args)
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(!output.status.success(), "{args:?} ran");
+        assert!(stderr.contains("usage: bench"), "{args:?}: {stderr}");
+        assert!(!stderr.contains("panicked"), "{args:?}: {stderr}");
+    }
+}
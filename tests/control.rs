@@ -0,0 +1,201 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpStream, UdpSocket},
+    thread,
+    time::{Duration, Instant},
+};
+
+use bevy::prelude::*;
+use bevy_matrix::{
+    matrix_control::*, matrix_field::MatrixField, matrix_letter::MatrixLetterSettings,
+    matrix_strip::MatrixStripSettings,
+};
+use serde_json::{json, Value};
+
+#[derive(Resource, Default)]
+struct Received(Vec<String>);
+
+fn collect_messages(mut messages: EventReader<MatrixMessage>, mut received: ResMut<Received>) {
+    received
+        .0
+        .extend(messages.read().map(|message| message.text.clone()));
+}
+
+fn app() -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugins(MatrixControlPlugin {
+            addr: "127.0.0.1:0".to_string(),
+            osc_addr: Some("127.0.0.1:0".to_string()),
+        })
+        .init_resource::<MatrixStripSettings>()
+        .init_resource::<MatrixLetterSettings>()
+        .init_resource::<Received>()
+        .add_systems(Update, collect_messages);
+    app.world_mut()
+        .spawn((MatrixField::default(), Name::new("front")));
+    app.world_mut()
+        .spawn((MatrixField::default(), Name::new("back")));
+    app
+}
+
+/// Sends `lines` from a client thread and runs the app until every line is
+/// answered.
+fn request(app: &mut App, lines: &[Value]) -> Vec<Value> {
+    let addr = app.world().resource::<MatrixControlAddrs>().tcp.unwrap();
+    let lines: Vec<String> = lines.iter().map(Value::to_string).collect();
+    let client = thread::spawn(move || {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let mut replies = BufReader::new(stream.try_clone().unwrap());
+        lines
+            .iter()
+            .map(|line| {
+                writeln!(stream, "{line}").unwrap();
+                let mut reply = String::new();
+                replies.read_line(&mut reply).unwrap();
+                serde_json::from_str(&reply).unwrap()
+            })
+            .collect()
+    });
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !client.is_finished() {
+        assert!(Instant::now() < deadline, "control client timed out");
+        app.update();
+        thread::sleep(Duration::from_millis(1));
+    }
+    client.join().unwrap()
+}
+
+fn field<'a>(app: &'a mut App, wanted: &str) -> &'a MatrixField {
+    let world = app.world_mut();
+    let mut fields = world.query::<(&MatrixField, &Name)>();
+    let (field, _) = fields
+        .iter(world)
+        .find(|(_, name)| name.as_str() == wanted)
+        .unwrap();
+    field
+}
+
+#[test]
+fn sets_and_gets_named_fields() {
+    let mut app = app();
+    let replies = request(
+        &mut app,
+        &[
+            json!({"cmd": "set", "key": "density", "value": 50.0, "field": "front"}),
+            json!({"cmd": "get", "key": "density", "field": "front"}),
+            json!({"cmd": "set", "key": "palette.head", "value": "#ff0000"}),
+            json!({"cmd": "set", "key": "max_length", "value": 12}),
+            json!({"cmd": "get", "key": "max_length", "field": "back"}),
+        ],
+    );
+    for reply in &replies {
+        assert_eq!(reply["ok"], json!(true), "{reply}");
+    }
+    assert_eq!(replies[1]["value"], json!(50.0));
+    assert_eq!(replies[4]["value"], json!(12));
+
+    assert_eq!(field(&mut app, "front").spawn_interval, 1.0 / 50.0);
+    assert_eq!(
+        field(&mut app, "back").spawn_interval,
+        MatrixField::default().spawn_interval
+    );
+    assert_eq!(
+        field(&mut app, "back").palette.head,
+        Color::srgb(1.0, 0.0, 0.0)
+    );
+}
+
+#[test]
+fn rejects_bad_commands() {
+    let mut app = app();
+    app.insert_resource(MatrixControlAllowList(vec![
+        ControlKey::Density,
+        ControlKey::MaxLength,
+    ]));
+    let replies = request(
+        &mut app,
+        &[
+            json!({"cmd": "set", "key": "gravity", "value": 1.0}),
+            json!({"cmd": "set", "key": "speed", "value": 2.0}),
+            json!({"cmd": "set", "key": "max_length", "value": 0.5}),
+            json!({"cmd": "set", "key": "density", "value": 5.0, "field": "side"}),
+            json!({"cmd": "list"}),
+        ],
+    );
+    for (reply, error) in replies.iter().zip([
+        "unknown key 'gravity'",
+        "key 'speed' is not settable",
+        "expected a whole number of at least 1, got 0.5",
+        "no such field",
+    ]) {
+        assert_eq!(reply["ok"], json!(false), "{reply}");
+        assert_eq!(reply["error"], json!(error));
+    }
+    assert_eq!(replies[4]["value"], json!(["density", "max_length"]));
+    assert_eq!(field(&mut app, "front").max_length, 40);
+}
+
+#[test]
+fn sets_strip_and_letter_settings() {
+    let mut app = app();
+    let replies = request(
+        &mut app,
+        &[
+            json!({"cmd": "set", "key": "strip.drift", "value": [0.5, -2.0]}),
+            json!({"cmd": "set", "key": "letter.flip_interval", "value": 0.25}),
+            json!({"cmd": "get", "key": "strip.drift"}),
+        ],
+    );
+    for reply in &replies {
+        assert_eq!(reply["ok"], json!(true), "{reply}");
+    }
+    assert_eq!(replies[2]["value"], json!([0.5, -2.0]));
+    assert_eq!(
+        app.world().resource::<MatrixStripSettings>().drift,
+        Vec2::new(0.5, -2.0)
+    );
+    assert_eq!(
+        app.world().resource::<MatrixLetterSettings>().flip_interval,
+        0.25
+    );
+}
+
+#[test]
+fn sends_message_events() {
+    let mut app = app();
+    let replies = request(
+        &mut app,
+        &[json!({"cmd": "message", "text": "wake up, neo"})],
+    );
+    assert_eq!(replies[0]["ok"], json!(true));
+    assert_eq!(app.world().resource::<Received>().0, ["wake up, neo"]);
+}
+
+fn osc_string(packet: &mut Vec<u8>, s: &str) {
+    packet.extend_from_slice(s.as_bytes());
+    // Nul terminated and padded to four bytes.
+    packet.resize((packet.len() + 4) & !3, 0);
+}
+
+#[test]
+fn applies_osc_messages() {
+    let mut app = app();
+    let addr = app.world().resource::<MatrixControlAddrs>().osc.unwrap();
+    let mut packet = Vec::new();
+    osc_string(&mut packet, "/matrix/set/speed");
+    osc_string(&mut packet, ",f");
+    packet.extend_from_slice(&2.5f32.to_be_bytes());
+    UdpSocket::bind("127.0.0.1:0")
+        .unwrap()
+        .send_to(&packet, addr)
+        .unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while field(&mut app, "front").speed != 2.5 {
+        assert!(Instant::now() < deadline, "OSC message not applied");
+        app.update();
+        thread::sleep(Duration::from_millis(1));
+    }
+    assert_eq!(field(&mut app, "back").speed, 2.5);
+}
@@ -0,0 +1,83 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, render::extract_component::ExtractComponent, time::TimeUpdateStrategy};
+use bevy_matrix::{
+    matrix_glitch::{GlitchKind, MatrixGlitch, MatrixGlitchPlugin, MatrixGlitches},
+    post::MatrixPost,
+};
+
+const FRAME: Duration = Duration::from_millis(10);
+
+fn app() -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, MatrixGlitchPlugin))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(FRAME));
+    // The first update has no delta.
+    app.update();
+    app
+}
+
+/// Runs until `seconds` into the burst and returns the glitch levels the
+/// post shader gets.
+fn levels_at(app: &mut App, frames: &mut u32, seconds: f32) -> Vec4 {
+    while (*frames as f32) < seconds / FRAME.as_secs_f32() - 0.5 {
+        app.update();
+        *frames += 1;
+    }
+    let glitches = app.world().resource::<MatrixGlitches>();
+    MatrixPost::default().uniform(Some(glitches))[2]
+}
+
+#[test]
+fn burst_timeline() {
+    let mut app = app();
+    app.world_mut()
+        .send_event(MatrixGlitch::new(GlitchKind::FrozenBlocks, 1.0).with_intensity(0.8));
+    let mut frames = 0;
+    let close = |level: Vec4, expected: f32| {
+        assert!(
+            (level.z - expected).abs() < 1e-3 && level.x == 0.0 && level.y == 0.0 && level.w == 0.0,
+            "{level}, expected {expected} frozen blocks"
+        );
+    };
+
+    // Half way through the attack.
+    close(levels_at(&mut app, &mut frames, 0.05), 0.4);
+    // The peak, at the end of the attack.
+    close(levels_at(&mut app, &mut frames, 0.1), 0.8);
+    // Half way through the decay.
+    close(levels_at(&mut app, &mut frames, 0.55), 0.8 * 0.25);
+    assert!(app.world().resource::<MatrixGlitches>().is_active());
+    // Over.
+    close(levels_at(&mut app, &mut frames, 1.0), 0.0);
+    close(levels_at(&mut app, &mut frames, 1.2), 0.0);
+    assert!(!app.world().resource::<MatrixGlitches>().is_active());
+}
+
+#[test]
+fn bursts_keep_the_strongest_level() {
+    let mut app = app();
+    app.world_mut()
+        .send_event(MatrixGlitch::new(GlitchKind::Tear, 1.0).with_intensity(0.5));
+    app.world_mut()
+        .send_event(MatrixGlitch::new(GlitchKind::Tear, 0.2));
+    let mut frames = 0;
+    // The short burst peaks over the long one.
+    let level = levels_at(&mut app, &mut frames, 0.02);
+    assert!((level.x - 1.0).abs() < 1e-3, "{level}");
+    // Then only the long one is left.
+    let level = levels_at(&mut app, &mut frames, 0.5);
+    assert!(
+        (level.x - 0.5 * (1.0 - 0.4 / 0.9f32).powi(2)).abs() < 1e-3,
+        "{level}"
+    );
+}
+
+#[test]
+fn disabled_posts_still_glitch() {
+    let post = MatrixPost {
+        enabled: false,
+        ..default()
+    };
+    assert_eq!(MatrixPost::extract_component(&post), Some(post));
+}
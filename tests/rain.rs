@@ -0,0 +1,483 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use bevy::{
+    asset::AssetEvent, ecs::entity::EntityHashMap, prelude::*, text::FontLoader,
+    time::TimeUpdateStrategy, utils::HashSet,
+};
+use bevy_matrix::{
+    matrix_budget::{MatrixBudgetPlugin, RainBudget},
+    matrix_field::{field_scene, MatrixField, MatrixFieldBundle, MatrixFieldPlugin, RainMode},
+    matrix_fog::{MatrixFog, MatrixFogPlugin},
+    matrix_glyphs::{GlyphMix, GlyphSet, MatrixGlyphs},
+    matrix_letter::{
+        LetterPhase, LetterSpawned, MatrixLetter, MatrixLetterBundle, MatrixLetterPlugin,
+        MatrixLetterSettings,
+    },
+    matrix_preset::MatrixPreset,
+    matrix_shader_rain::{MatrixShaderRainPlugin, ShaderRainMaterial},
+    matrix_state::{restore_state, save_state, MatrixState},
+    matrix_strip::{
+        DepthCurve, MatrixStripBundle, MatrixStripPlugin, MatrixStripSettings, StripSpawned,
+    },
+};
+use bevy_tweening::Animator;
+
+const FRAME: Duration = Duration::from_nanos(16_666_667);
+
+/// Headless app with the rain plugins, stepping `frame` per update.
+fn app(frame: Duration) -> App {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        AssetPlugin::default(),
+        TransformPlugin,
+        HierarchyPlugin,
+    ))
+    .init_asset::<Font>()
+    .init_asset_loader::<FontLoader>()
+    .add_plugins((MatrixFieldPlugin, MatrixStripPlugin, MatrixLetterPlugin))
+    .insert_resource(TimeUpdateStrategy::ManualDuration(frame));
+    app
+}
+
+/// Runs the app until the glyph fonts are loaded.
+fn load_glyphs(app: &mut App) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !app.world().resource::<MatrixGlyphs>().is_ready() {
+        assert!(Instant::now() < deadline, "fonts didn't load");
+        app.update();
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+/// An app that has been raining for `seconds`, then stopped the clock so
+/// nothing moves between captures.
+fn raining_app(seconds: f32) -> (App, Entity) {
+    let mut app = app(FRAME);
+    load_glyphs(&mut app);
+    let field = app
+        .world_mut()
+        .spawn((MatrixFieldBundle::default(), Name::new("rain")))
+        .id();
+    for _ in 0..(seconds / FRAME.as_secs_f32()) as usize {
+        app.update();
+    }
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO));
+    app.update();
+    (app, field)
+}
+
+fn fades(state: &MatrixState) -> usize {
+    state
+        .fields
+        .iter()
+        .flat_map(|field| &field.strips)
+        .flat_map(|strip| &strip.letters)
+        .filter(|letter| letter.fade.is_some())
+        .count()
+}
+
+#[test]
+fn field_scene_round_trip() {
+    let (mut app, field) = raining_app(2.0);
+    let scene = field_scene(app.world(), field);
+    let before = save_state(app.world_mut());
+    assert!(fades(&before) > 0, "no letter started fading");
+
+    let mut loaded = self::app(Duration::ZERO);
+    scene
+        .write_to_world(loaded.world_mut(), &mut EntityHashMap::default())
+        .unwrap();
+    loaded.update();
+    assert_eq!(save_state(loaded.world_mut()), before);
+}
+
+#[test]
+fn state_round_trip() {
+    let mut app = app(FRAME);
+    load_glyphs(&mut app);
+    app.world_mut()
+        .spawn((MatrixFieldBundle::default(), Name::new("rain")));
+    for _ in 0..120 {
+        app.update();
+        // Letters requested this frame are captured as they will be.
+        let state = save_state(app.world_mut());
+        assert_eq!(save_state(app.world_mut()), state);
+    }
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO));
+    app.update();
+    let before = save_state(app.world_mut());
+    assert!(!before.fields[0].strips.is_empty(), "nothing is raining");
+    assert_eq!(
+        MatrixState::from_ron(&before.to_ron().unwrap()).unwrap(),
+        before
+    );
+
+    let mut restored = self::app(Duration::ZERO);
+    restored
+        .world_mut()
+        .spawn((MatrixFieldBundle::default(), Name::new("rain")));
+    restore_state(restored.world_mut(), &before);
+    restored.update();
+    assert_eq!(save_state(restored.world_mut()), before);
+}
+
+#[test]
+fn letter_spawned_at_letter_position() {
+    let mut app = app(FRAME);
+    load_glyphs(&mut app);
+    app.world_mut().resource_mut::<MatrixStripSettings>().drift = Vec2::ZERO;
+    let field = MatrixField {
+        spawnrate: 100.0..200.0,
+        ..Default::default()
+    };
+    let field = app
+        .world_mut()
+        .spawn(MatrixFieldBundle::new(field).with_transform(
+            Transform::from_xyz(40.0, -20.0, 0.0).with_scale(Vec3::new(2.0, 0.5, 1.0)),
+        ))
+        .id();
+    // A strip and its first letter spawned together, before the strip has
+    // a global transform.
+    let strip = app
+        .world_mut()
+        .spawn(MatrixStripBundle::new(Vec3::new(3.0, 2.0, -1.0)))
+        .set_parent(field)
+        .id();
+    app.world_mut()
+        .spawn(MatrixLetterBundle::new(Vec3::new(0.0, -1.0, 0.0)))
+        .set_parent(strip);
+    let mut checked = 0;
+    for _ in 0..60 {
+        app.update();
+        for event in read::<LetterSpawned>(&app) {
+            let letter = app.world().get::<GlobalTransform>(event.entity).unwrap();
+            assert!(
+                letter.translation().distance(event.position) < 1e-3,
+                "{} sent at {}",
+                letter.translation(),
+                event.position
+            );
+            checked += 1;
+        }
+    }
+    assert!(checked > 0, "no letter spawned");
+}
+
+fn read<E: Event + Clone>(app: &App) -> Vec<E> {
+    let events = app.world().resource::<Events<E>>();
+    events.get_reader().read(events).cloned().collect()
+}
+
+#[test]
+fn reuses_letters_and_strips() {
+    let mut app = app(FRAME);
+    load_glyphs(&mut app);
+    // Short strips, so many of them come and go.
+    let field = MatrixField {
+        lifetime: 0.2..0.5,
+        max_length: 10,
+        spawn_interval: 0.02,
+        ..Default::default()
+    };
+    app.world_mut().spawn(MatrixFieldBundle::new(field));
+
+    let (mut letters, mut strips) = (HashSet::new(), HashSet::new());
+    let (mut letters_spawned, mut strips_spawned) = (0, 0);
+    let mut peak = 0;
+    for frame in 0..1200 {
+        app.update();
+        for event in read::<LetterSpawned>(&app) {
+            // A reused letter doesn't carry the fade of its last strip.
+            assert!(app
+                .world()
+                .get::<Animator<MatrixLetter>>(event.entity)
+                .is_none());
+            letters.insert(event.entity);
+            letters_spawned += 1;
+        }
+        for event in read::<StripSpawned>(&app) {
+            strips.insert(event.entity);
+            strips_spawned += 1;
+        }
+        let entities = app.world().entities().len();
+        if frame < 600 {
+            peak = peak.max(entities);
+        } else {
+            assert!(
+                entities <= peak,
+                "{entities} entities, at most {peak} before"
+            );
+        }
+    }
+    assert!(
+        letters.len() * 4 < letters_spawned,
+        "{letters_spawned} letters from {} entities",
+        letters.len()
+    );
+    assert!(
+        strips.len() * 4 < strips_spawned,
+        "{strips_spawned} strips from {} entities",
+        strips.len()
+    );
+}
+
+#[test]
+fn budget_counts_live_rain() {
+    let mut app = app(FRAME);
+    app.add_plugins(MatrixBudgetPlugin);
+    load_glyphs(&mut app);
+    app.world_mut().spawn(MatrixFieldBundle::default());
+    for _ in 0..60 {
+        app.update();
+    }
+    // The app may despawn letters the pool still lists.
+    let world = app.world_mut();
+    let pooled: Vec<Entity> = world
+        .query::<(Entity, &LetterPhase)>()
+        .iter(world)
+        .filter(|(_, phase)| **phase == LetterPhase::Pooled)
+        .map(|(entity, _)| entity)
+        .collect();
+    assert!(!pooled.is_empty());
+    for entity in pooled {
+        world.despawn(entity);
+    }
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO));
+    app.update();
+
+    let world = app.world_mut();
+    let live = world
+        .query::<&LetterPhase>()
+        .iter(world)
+        .filter(|phase| **phase != LetterPhase::Pooled)
+        .count();
+    let budget = app.world().resource::<RainBudget>();
+    assert!(budget.strips() > 0);
+    assert_eq!(budget.letters(), live);
+}
+
+#[test]
+fn preset_reports_glyph_problems() {
+    let mut app = app(FRAME);
+    load_glyphs(&mut app);
+    app.world_mut()
+        .spawn((MatrixFieldBundle::default(), Name::new("rain")));
+    let mut preset = MatrixPreset::capture(app.world_mut());
+    preset.fields[0].glyphs = Some(GlyphMix::Only(3));
+    preset.glyph_sets = Some(vec![GlyphSet::new(
+        "matrix",
+        "ab\u{2603}",
+        ["fonts/matrix.ttf"],
+    )]);
+
+    // The font is loaded already, so the set is checked right away.
+    let problems = preset.apply(app.world_mut()).unwrap();
+    assert_eq!(
+        problems,
+        [
+            "Field \"rain\": glyph set 3 doesn't exist, there are 1",
+            "Glyph set \"matrix\": no font covers \"\u{2603}\", leaving them out",
+        ]
+    );
+    assert_eq!(
+        app.world().resource::<MatrixGlyphs>().missing(0),
+        ['\u{2603}']
+    );
+    // Strips of the field mix the sets meanwhile.
+    for _ in 0..30 {
+        app.update();
+    }
+    assert!(!save_state(app.world_mut()).fields[0].strips.is_empty());
+}
+
+#[test]
+fn rejects_presets_that_break_the_spawner() {
+    let mut app = app(FRAME);
+    load_glyphs(&mut app);
+    app.world_mut()
+        .spawn((MatrixFieldBundle::default(), Name::new("rain")));
+    let good = MatrixPreset::capture(app.world_mut());
+    for (change, error) in [
+        (
+            (|p: &mut MatrixPreset| p.fields[0].lifetime = [10.0, 10.0]) as fn(&mut MatrixPreset),
+            "Field \"rain\": lifetime: expected min < max, got [10, 10]",
+        ),
+        (
+            |p| p.fields[0].spawnrate = [20.0, 5.0],
+            "Field \"rain\": spawnrate: expected min < max, got [20, 5]",
+        ),
+        (
+            |p| p.fields[0].spawn_interval = 0.0,
+            "Field \"rain\": spawn_interval: expected a positive number, got 0",
+        ),
+    ] {
+        let mut preset = good.clone();
+        change(&mut preset);
+        preset.fade_time = Some(9.0);
+        assert_eq!(preset.apply(app.world_mut()), Err(error.to_string()));
+        // Nothing of the preset is applied.
+        assert_eq!(MatrixPreset::capture(app.world_mut()), good);
+        for _ in 0..10 {
+            app.update();
+        }
+    }
+}
+
+#[test]
+fn zero_size_strips_round_trip() {
+    let mut app = app(FRAME);
+    load_glyphs(&mut app);
+    app.world_mut()
+        .resource_mut::<MatrixStripSettings>()
+        .size_curve = DepthCurve::Constant(0.0);
+    app.world_mut()
+        .spawn((MatrixFieldBundle::default(), Name::new("rain")));
+    for _ in 0..60 {
+        app.update();
+    }
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO));
+    app.update();
+    let before = save_state(app.world_mut());
+    assert!(!before.fields[0].strips.is_empty());
+    assert!(before.fields[0].strips.iter().all(|s| s.cell_size == 0.0));
+
+    let mut restored = self::app(Duration::ZERO);
+    restored
+        .world_mut()
+        .spawn((MatrixFieldBundle::default(), Name::new("rain")));
+    restore_state(restored.world_mut(), &before);
+    restored.update();
+    assert_eq!(save_state(restored.world_mut()), before);
+}
+
+#[test]
+fn first_letter_sized_like_the_rest() {
+    let mut app = app(FRAME);
+    load_glyphs(&mut app);
+    let field = app
+        .world_mut()
+        .spawn((
+            MatrixFieldBundle::new(MatrixField {
+                spawn_interval: f32::INFINITY,
+                ..Default::default()
+            })
+            .with_transform(Transform::from_scale(Vec3::splat(0.25))),
+            Name::new("rain"),
+        ))
+        .id();
+    // The strip and its first letter are spawned before any transform
+    // propagation.
+    let strip = app
+        .world_mut()
+        .spawn(MatrixStripBundle::new(Vec3::ZERO).with_max_length(5))
+        .set_parent(field)
+        .id();
+    app.world_mut()
+        .spawn(MatrixLetterBundle::new(Vec3::ZERO))
+        .set_parent(strip);
+    for _ in 0..60 {
+        app.update();
+    }
+    let state = save_state(app.world_mut());
+    let letters = &state.fields[0].strips[0].letters;
+    assert!(letters.len() > 1);
+    let expected = app
+        .world()
+        .resource::<MatrixLetterSettings>()
+        .font_size(0.25);
+    for letter in letters {
+        assert_eq!(letter.font_size, expected);
+    }
+}
+
+#[test]
+fn shader_rain_material_changes_with_its_inputs() {
+    let mut app = app(FRAME);
+    app.init_asset::<Mesh>()
+        .init_asset::<Image>()
+        .add_plugins(MatrixShaderRainPlugin);
+    load_glyphs(&mut app);
+    let field = app
+        .world_mut()
+        .spawn(MatrixFieldBundle::new(MatrixField {
+            mode: RainMode::Shader,
+            ..Default::default()
+        }))
+        .id();
+    let modified = |app: &mut App| {
+        app.update();
+        app.world_mut()
+            .resource_mut::<Events<AssetEvent<ShaderRainMaterial>>>()
+            .drain()
+            .filter(|event| matches!(event, AssetEvent::Modified { .. }))
+            .count()
+    };
+    assert!(modified(&mut app) > 0);
+    modified(&mut app);
+    // The rain moves in the shader alone.
+    for _ in 0..30 {
+        assert_eq!(modified(&mut app), 0);
+    }
+    app.world_mut()
+        .get_mut::<MatrixField>(field)
+        .unwrap()
+        .spawn_interval = 0.1;
+    assert_eq!(modified(&mut app), 1);
+    assert_eq!(modified(&mut app), 0);
+    app.world_mut()
+        .resource_mut::<MatrixStripSettings>()
+        .fade_time = 1.0;
+    assert_eq!(modified(&mut app), 1);
+    app.world_mut()
+        .get_mut::<Transform>(field)
+        .unwrap()
+        .translation
+        .z = -2.0;
+    // Seen once the transform has propagated.
+    assert_eq!(modified(&mut app) + modified(&mut app), 1);
+    assert_eq!(modified(&mut app), 0);
+}
+
+#[test]
+fn fog_changes_show_the_same_frame() {
+    let mut app = app(FRAME);
+    app.add_plugins(MatrixFogPlugin);
+    load_glyphs(&mut app);
+    app.world_mut().spawn(MatrixFieldBundle::default());
+    for _ in 0..60 {
+        app.update();
+    }
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO));
+    app.update();
+    let colors = |app: &mut App| {
+        let world = app.world_mut();
+        world
+            .query::<(&Text, &LetterPhase)>()
+            .iter(world)
+            .filter(|(_, phase)| **phase != LetterPhase::Pooled)
+            .map(|(text, _)| text.sections[0].style.color)
+            .collect::<Vec<_>>()
+    };
+    let before = colors(&mut app);
+    assert!(!before.is_empty());
+
+    *app.world_mut().resource_mut::<MatrixFog>() = MatrixFog {
+        color: Color::srgb(1.0, 0.0, 0.0),
+        near: 10.0,
+        far: 9.0,
+        strength: 1.0,
+        ..Default::default()
+    };
+    app.update();
+    let fogged = colors(&mut app);
+    assert!(fogged
+        .iter()
+        .zip(&before)
+        .all(|(fogged, before)| fogged != before));
+    app.update();
+    assert_eq!(colors(&mut app), fogged);
+}